@@ -0,0 +1,371 @@
+use crate::settings::{ConfigSettings, Override};
+use crate::words::WordMatcher;
+use glob::Pattern;
+use log::error;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+pub(crate) static DEFAULT_DICTIONARY: &str = "en_us";
+pub(crate) static DEFAULT_MAX_SUGGESTIONS: usize = 5;
+
+/// Indices of the overrides applied to a file, and the file's dictionaries
+type OverrideKey = (Vec<usize>, Vec<String>);
+
+/// Settings in effect for the files in one directory, after merging the global
+/// config, the project config and any config files in directories between the
+/// project root and this one.
+#[derive(Debug)]
+pub struct DirectoryConfig {
+    settings: ConfigSettings,
+    /// Relative path patterns are matched against paths relative to this
+    root: Option<PathBuf>,
+    ignore_patterns: Vec<Regex>,
+    allowed_words: WordMatcher,
+    flagged_words: WordMatcher,
+    /// Configs with overrides applied, see `with_overrides`
+    overridden: RwLock<HashMap<OverrideKey, Arc<DirectoryConfig>>>,
+}
+
+impl DirectoryConfig {
+    pub(crate) fn new(settings: ConfigSettings, root: Option<PathBuf>) -> Self {
+        let mut ignore_patterns: Vec<Regex> = settings
+            .ignore_patterns
+            .iter()
+            .filter_map(|pattern| {
+                Regex::new(pattern)
+                    .inspect_err(|e| error!("Invalid ignore pattern '{}': {}", pattern, e))
+                    .ok()
+            })
+            .collect();
+        // Case-sensitive words written as configured are correct as a whole,
+        // don't check the parts they would be split into
+        ignore_patterns.extend(
+            settings
+                .case_sensitive_words
+                .iter()
+                .filter_map(|word| Regex::new(&format!(r"\b{}\b", regex::escape(word))).ok()),
+        );
+        Self {
+            allowed_words: WordMatcher::new(&settings.words),
+            flagged_words: WordMatcher::new(&settings.flag_words),
+            settings,
+            root,
+            ignore_patterns,
+            overridden: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Get the dictionary IDs configured for this directory
+    pub fn get_dictionary_ids(&self) -> Vec<String> {
+        if self.settings.dictionaries.is_empty() {
+            return vec![DEFAULT_DICTIONARY.to_string()];
+        }
+        self.settings.dictionaries.clone()
+    }
+
+    /// Get dictionary IDs to use for a file. Dictionaries configured for patterns
+    /// matching the path replace the top-level `dictionaries`.
+    pub fn get_dictionary_ids_for_path<P: AsRef<Path>>(&self, path: P) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .settings
+            .path_dictionaries
+            .iter()
+            .filter(|p| path_matches_any(self.root.as_deref(), path.as_ref(), &p.paths))
+            .flat_map(|p| p.dictionaries.clone())
+            .collect();
+        if ids.is_empty() {
+            return self.get_dictionary_ids();
+        }
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    pub fn should_detect_language(&self) -> bool {
+        self.settings.detect_language.unwrap_or(false)
+    }
+
+    pub fn get_max_suggestions(&self) -> usize {
+        self.settings
+            .max_suggestions
+            .unwrap_or(DEFAULT_MAX_SUGGESTIONS)
+    }
+
+    pub fn should_ignore_path<P: AsRef<Path>>(&self, path: P) -> bool {
+        path_matches_any(
+            self.root.as_deref(),
+            path.as_ref(),
+            &self.settings.ignore_paths,
+        )
+    }
+
+    pub fn is_allowed_word(&self, word: &str) -> bool {
+        self.allowed_words.matches(word)
+    }
+
+    pub fn should_flag_word(&self, word: &str) -> bool {
+        self.flagged_words.matches(word)
+    }
+
+    /// Get the configured spelling of a word from `case_sensitive_words` that is
+    /// written with different casing. All-lowercase and all-caps words, like
+    /// parts of identifiers and constants, aren't considered miscased.
+    pub fn get_correct_casing(&self, word: &str) -> Option<&str> {
+        let words = &self.settings.case_sensitive_words;
+        if !word.chars().any(char::is_uppercase)
+            || !word.chars().any(char::is_lowercase)
+            || words.iter().any(|w| w == word)
+        {
+            return None;
+        }
+        words
+            .iter()
+            .find(|w| w.to_lowercase() == word.to_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Get the compiled user-defined ignore patterns, invalid ones are left out.
+    /// Also includes patterns matching the case-sensitive words as written.
+    pub fn get_ignore_patterns(&self) -> &[Regex] {
+        &self.ignore_patterns
+    }
+
+    /// Apply the `[[overrides]]` matching a file's path or any of the IDs of
+    /// its language. Returns None when none match. Files the same overrides
+    /// apply to share the resulting config.
+    pub(crate) fn with_overrides(
+        &self,
+        path: Option<&Path>,
+        language_ids: &[&str],
+    ) -> Option<Arc<Self>> {
+        let matching: Vec<usize> = self
+            .settings
+            .overrides
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| self.override_matches(o, path, language_ids))
+            .map(|(index, _)| index)
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // Resolve the dictionaries first, overrides change what the file uses
+        let dictionaries = match path {
+            Some(path) => self.get_dictionary_ids_for_path(path),
+            None => self.get_dictionary_ids(),
+        };
+        let key = (matching, dictionaries);
+        if let Some(config) = self.overridden.read().unwrap().get(&key) {
+            return Some(config.clone());
+        }
+
+        let mut settings = self.settings.clone();
+        settings.dictionaries = key.1.clone();
+        settings.path_dictionaries.clear();
+        for o in key.0.iter().map(|&index| &self.settings.overrides[index]) {
+            apply_override(
+                &mut settings.dictionaries,
+                &o.dictionaries,
+                &o.remove_dictionaries,
+            );
+            apply_override(&mut settings.words, &o.words, &o.remove_words);
+            apply_override(
+                &mut settings.flag_words,
+                &o.flag_words,
+                &o.remove_flag_words,
+            );
+            apply_override(
+                &mut settings.ignore_patterns,
+                &o.ignore_patterns,
+                &o.remove_ignore_patterns,
+            );
+        }
+        settings.overrides.clear();
+        let config = Arc::new(Self::new(settings, self.root.clone()));
+        self.overridden.write().unwrap().insert(key, config.clone());
+        Some(config)
+    }
+
+    fn override_matches(&self, o: &Override, path: Option<&Path>, language_ids: &[&str]) -> bool {
+        let path_matches = o.paths.is_empty()
+            || path.is_some_and(|path| path_matches_any(self.root.as_deref(), path, &o.paths));
+        let language_matches = o.languages.is_empty()
+            || language_ids
+                .iter()
+                .any(|id| o.languages.iter().any(|l| l.eq_ignore_ascii_case(id)));
+        path_matches && language_matches
+    }
+}
+
+fn apply_override(values: &mut Vec<String>, add: &[String], remove: &[String]) {
+    values.retain(|value| !remove.contains(value));
+    for value in add {
+        if !values.contains(value) {
+            values.push(value.clone());
+        }
+    }
+}
+
+/// Match a path against glob patterns, both as given and relative to `root`
+pub(crate) fn path_matches_any(root: Option<&Path>, path: &Path, patterns: &[String]) -> bool {
+    let relative_path = root.and_then(|root| path.strip_prefix(root).ok());
+    patterns.iter().any(|pattern| {
+        Pattern::new(pattern)
+            .is_ok_and(|p| p.matches_path(path) || relative_path.is_some_and(|r| p.matches_path(r)))
+    })
+}
+
+/// Path patterns in a config file apply to its own directory. Prefix them with
+/// that directory, relative to `root` where possible, so they still do after
+/// merging with the settings of parent directories.
+pub(crate) fn rebase_path_patterns(settings: &mut ConfigSettings, dir: &Path, root: Option<&Path>) {
+    let base = root
+        .and_then(|root| dir.strip_prefix(root).ok())
+        .unwrap_or(dir);
+    let prefix = Pattern::escape(&base.to_string_lossy());
+    let rebase = |pattern: &mut String| {
+        if !prefix.is_empty() && !Path::new(pattern.as_str()).is_absolute() {
+            *pattern = format!("{}/{}", prefix.trim_end_matches('/'), pattern);
+        }
+    };
+    settings.ignore_paths.iter_mut().for_each(rebase);
+    for path_dictionaries in &mut settings.path_dictionaries {
+        path_dictionaries.paths.iter_mut().for_each(rebase);
+    }
+    for config_override in &mut settings.overrides {
+        config_override.paths.iter_mut().for_each(rebase);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::PathDictionaries;
+
+    #[test]
+    fn test_rebase_path_patterns() {
+        let mut settings = ConfigSettings {
+            ignore_paths: vec!["dist/**".to_string(), "/abs/**".to_string()],
+            path_dictionaries: vec![PathDictionaries {
+                paths: vec!["docs/**".to_string()],
+                dictionaries: vec!["de".to_string()],
+            }],
+            ..Default::default()
+        };
+        rebase_path_patterns(
+            &mut settings,
+            Path::new("/repo/packages/a"),
+            Some(Path::new("/repo")),
+        );
+        assert_eq!(settings.ignore_paths, vec!["packages/a/dist/**", "/abs/**"]);
+        assert_eq!(
+            settings.path_dictionaries[0].paths,
+            vec!["packages/a/docs/**"]
+        );
+
+        let config = DirectoryConfig::new(settings, Some(PathBuf::from("/repo")));
+        assert!(config.should_ignore_path("/repo/packages/a/dist/index.js"));
+        assert!(!config.should_ignore_path("/repo/packages/b/dist/index.js"));
+        assert_eq!(
+            config.get_dictionary_ids_for_path("/repo/packages/a/docs/index.md"),
+            vec!["de"]
+        );
+        assert_eq!(
+            config.get_dictionary_ids_for_path("/repo/docs/index.md"),
+            vec!["en_us"]
+        );
+    }
+
+    #[test]
+    fn test_overrides() {
+        let settings = ConfigSettings {
+            words: vec!["keepme".to_string(), "dropme".to_string()],
+            overrides: vec![
+                Override {
+                    paths: vec!["**/*.sql".to_string()],
+                    dictionaries: vec!["sql".to_string()],
+                    ..Default::default()
+                },
+                Override {
+                    paths: vec!["tests/**".to_string()],
+                    languages: vec!["python".to_string()],
+                    words: vec!["fixtureword".to_string()],
+                    remove_words: vec!["dropme".to_string()],
+                    ignore_patterns: vec!["^x+$".to_string()],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let config = DirectoryConfig::new(settings, Some(PathBuf::from("/repo")));
+
+        let sql = config
+            .with_overrides(Some(Path::new("/repo/db/schema.sql")), &[])
+            .unwrap();
+        assert_eq!(sql.get_dictionary_ids(), vec!["en_us", "sql"]);
+        assert!(sql.is_allowed_word("dropme"));
+
+        // Both selectors have to match
+        assert!(
+            config
+                .with_overrides(Some(Path::new("/repo/tests/test_a.py")), &["rust"])
+                .is_none()
+        );
+        let test = config
+            .with_overrides(Some(Path::new("/repo/tests/test_a.py")), &["python"])
+            .unwrap();
+        assert!(test.is_allowed_word("fixtureword"));
+        assert!(test.is_allowed_word("keepme"));
+        assert!(!test.is_allowed_word("dropme"));
+        assert_eq!(test.get_ignore_patterns().len(), 1);
+        assert_eq!(test.get_dictionary_ids(), vec!["en_us"]);
+
+        // Files with the same overrides share the config
+        let other_test = config
+            .with_overrides(Some(Path::new("/repo/tests/test_b.py")), &["python"])
+            .unwrap();
+        assert!(Arc::ptr_eq(&test, &other_test));
+        assert!(!Arc::ptr_eq(&test, &sql));
+    }
+
+    #[test]
+    fn test_case_sensitive_words() {
+        let settings = ConfigSettings {
+            case_sensitive_words: vec!["GitHub".to_string(), "iOS".to_string()],
+            ..Default::default()
+        };
+        let config = DirectoryConfig::new(settings, None);
+        assert_eq!(config.get_correct_casing("Github"), Some("GitHub"));
+        assert_eq!(config.get_correct_casing("Ios"), Some("iOS"));
+        assert_eq!(config.get_correct_casing("GitHub"), None);
+        assert_eq!(config.get_correct_casing("github"), None);
+        // Like parts of constants such as GITHUB_TOKEN
+        assert_eq!(config.get_correct_casing("GITHUB"), None);
+        assert_eq!(config.get_correct_casing("IOS"), None);
+        assert_eq!(config.get_correct_casing("Gitlab"), None);
+        assert!(
+            config
+                .get_ignore_patterns()
+                .iter()
+                .any(|p| p.is_match("on GitHub."))
+        );
+        assert!(
+            !config
+                .get_ignore_patterns()
+                .iter()
+                .any(|p| p.is_match("on Github."))
+        );
+    }
+
+    #[test]
+    fn test_invalid_ignore_patterns_are_skipped() {
+        let settings = ConfigSettings {
+            ignore_patterns: vec!["(".to_string(), "^foo$".to_string()],
+            ..Default::default()
+        };
+        let config = DirectoryConfig::new(settings, None);
+        assert_eq!(config.get_ignore_patterns().len(), 1);
+    }
+}
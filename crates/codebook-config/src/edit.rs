@@ -0,0 +1,313 @@
+use crate::settings::ConfigSettings;
+use std::io;
+use toml_edit::{Array, Decor, DocumentMut, Item, Table, TableLike};
+
+/// Write settings into the content of a config file, keeping its comments,
+/// key order and formatting. Only keys whose values changed are touched, and
+/// strings added to an array are inserted in sorted position. Keys are only
+/// removed if the settings the file held had a value for them, so explicit
+/// defaults and unknown keys stay.
+pub(crate) fn update_document(content: &str, settings: &ConfigSettings) -> io::Result<String> {
+    let mut document: DocumentMut = content
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let updated = serialize(settings)?;
+    // Without the settings the file held, no key is known to be removed
+    let previous = match toml::from_str::<ConfigSettings>(content) {
+        Ok(previous) => serialize(&previous)?,
+        Err(_) => DocumentMut::new(),
+    };
+    update_table(
+        document.as_table_mut(),
+        Some(previous.as_table()),
+        updated.as_table(),
+    );
+    Ok(document.to_string())
+}
+
+fn serialize(settings: &ConfigSettings) -> io::Result<DocumentMut> {
+    toml::to_string_pretty(settings)
+        .map_err(io::Error::other)?
+        .parse()
+        .map_err(io::Error::other)
+}
+
+fn update_table(
+    table: &mut dyn TableLike,
+    previous: Option<&dyn TableLike>,
+    updated: &dyn TableLike,
+) {
+    let removed: Vec<String> = table
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !updated.contains_key(key))
+        .filter(|key| previous.is_some_and(|previous| previous.contains_key(key)))
+        .collect();
+    for key in removed {
+        // Tables keep the keys the settings don't know
+        let previous_table = previous.and_then(|p| p.get(&key)?.as_table_like());
+        if let (Some(nested), Some(previous_table)) = (
+            table.get_mut(&key).and_then(Item::as_table_like_mut),
+            previous_table,
+        ) {
+            update_table(nested, Some(previous_table), &Table::new());
+            if !nested.is_empty() {
+                continue;
+            }
+        }
+        table.remove(&key);
+    }
+    for (key, updated_item) in updated.iter() {
+        match table.get_mut(key) {
+            Some(item) => update_item(item, previous.and_then(|p| p.get(key)), updated_item),
+            None => {
+                table.insert(key, updated_item.clone());
+            }
+        }
+    }
+}
+
+fn update_item(item: &mut Item, previous: Option<&Item>, updated: &Item) {
+    if let (Some(table), Some(updated_table)) = (item.as_table_like_mut(), updated.as_table_like())
+    {
+        update_table(table, previous.and_then(Item::as_table_like), updated_table);
+        return;
+    }
+    if let (Some(array), Some(updated_array)) = (item.as_array_mut(), updated.as_array()) {
+        let is_strings = |array: &Array| array.iter().all(|value| value.is_str());
+        if is_strings(array) && is_strings(updated_array) {
+            update_strings(array, updated_array);
+            return;
+        }
+    }
+    if to_value(item) == to_value(updated) {
+        return;
+    }
+    match (item.as_value_mut(), updated.as_value()) {
+        (Some(value), Some(updated_value)) => {
+            let decor = value.decor().clone();
+            *value = updated_value.clone();
+            *value.decor_mut() = decor;
+        }
+        _ => *item = updated.clone(),
+    }
+}
+
+/// Remove the strings no longer in `updated` and insert the new ones. The file
+/// may hold strings before they were lowercased, they match either way.
+fn update_strings(array: &mut Array, updated: &Array) {
+    let updated: Vec<&str> = updated.iter().filter_map(|value| value.as_str()).collect();
+    let matches = |written: &str, value: &str| written == value || written.to_lowercase() == value;
+    let first_decor = array.get(0).map(|first| first.decor().clone());
+    array.retain(|value| {
+        value
+            .as_str()
+            .is_some_and(|written| updated.iter().any(|v| matches(written, v)))
+    });
+    // A string that became the first one takes the place of the removed one
+    if let (Some(first), Some(decor)) = (array.get_mut(0), first_decor) {
+        *first.decor_mut() = decor;
+    }
+    for value in updated {
+        if array
+            .iter()
+            .any(|written| written.as_str().is_some_and(|w| matches(w, value)))
+        {
+            continue;
+        }
+        let index = array
+            .iter()
+            .position(|written| {
+                written
+                    .as_str()
+                    .is_some_and(|w| w.to_lowercase() > value.to_lowercase())
+            })
+            .unwrap_or(array.len());
+        insert_string(array, index, value);
+    }
+}
+
+/// Insert a string, formatted like the strings already in the array
+fn insert_string(array: &mut Array, index: usize, value: &str) {
+    // The first string may be formatted differently, like `["a", "b"]`
+    let decor = match (array.get(0), array.get(1)) {
+        (_, Some(second)) => without_comments(second.decor()),
+        (Some(first), None) if is_multiline(first.decor()) => without_comments(first.decor()),
+        _ => Decor::new(" ", ""),
+    };
+    array.insert(index, value);
+    if index == 0 && array.len() > 1 {
+        let first_decor = array.get(1).unwrap().decor().clone();
+        *array.get_mut(0).unwrap().decor_mut() = first_decor;
+        *array.get_mut(1).unwrap().decor_mut() = decor;
+    } else if array.len() > 1 {
+        *array.get_mut(index).unwrap().decor_mut() = decor;
+    }
+}
+
+fn prefix(decor: &Decor) -> &str {
+    decor
+        .prefix()
+        .and_then(|prefix| prefix.as_str())
+        .unwrap_or_default()
+}
+
+fn is_multiline(decor: &Decor) -> bool {
+    prefix(decor).contains('\n')
+}
+
+/// The line break and indentation of a decor, without comments above the value
+fn without_comments(decor: &Decor) -> Decor {
+    let prefix = prefix(decor);
+    match prefix.rfind('\n') {
+        Some(index) => Decor::new(&prefix[index..], ""),
+        None => Decor::new(prefix, ""),
+    }
+}
+
+/// The value of an item, ignoring its formatting
+fn to_value(item: &Item) -> Option<toml::Value> {
+    let mut document = DocumentMut::new();
+    document.insert("value", item.clone());
+    toml::from_str::<toml::Table>(&document.to_string())
+        .ok()?
+        .remove("value")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(content: &str, update: impl FnOnce(&mut ConfigSettings)) -> String {
+        let mut settings: ConfigSettings = toml::from_str(content).unwrap();
+        update(&mut settings);
+        update_document(content, &settings).unwrap()
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let content = r#"# Project words
+words = ["Zebra", "apple"]  # not sorted
+
+dictionaries = [ "EN_US" ]
+max_suggestions = 3
+
+[download]
+offline = true # for CI
+"#;
+        assert_eq!(update(content, |_| {}), content);
+    }
+
+    #[test]
+    fn test_insert_words() {
+        let content = r#"# Project words
+words = [
+    "alpha",
+    # Greek letters
+    "gamma",
+]
+flag_words = ["bad", "worse"]
+ignore_paths = ["target"]
+"#;
+        let updated = update(content, |settings| {
+            settings
+                .words
+                .extend(["beta", "aa", "zeta"].map(String::from));
+            settings.flag_words.push("awful".to_string());
+            settings.ignore_paths.push("dist".to_string());
+            settings.max_suggestions = Some(3);
+        });
+        assert_eq!(
+            updated,
+            r#"# Project words
+words = [
+    "aa",
+    "alpha",
+    "beta",
+    # Greek letters
+    "gamma",
+    "zeta",
+]
+flag_words = ["awful", "bad", "worse"]
+ignore_paths = ["dist", "target"]
+max_suggestions = 3
+"#
+        );
+    }
+
+    #[test]
+    fn test_remove_and_replace() {
+        let content = r#"words = ["Apple", "pear"] # fruit
+use_global = false
+
+[download]
+# Slow network
+read_timeout = 120
+offline = true
+"#;
+        let updated = update(content, |settings| {
+            settings.words.retain(|word| word != "apple");
+            settings.download.read_timeout = Some(300);
+            settings.download.offline = None;
+            settings.use_global = true;
+        });
+        assert_eq!(
+            updated,
+            r#"words = ["pear"] # fruit
+
+[download]
+# Slow network
+read_timeout = 300
+"#
+        );
+    }
+
+    #[test]
+    fn test_keep_explicit_defaults() {
+        let content = r#"use_global = true
+words = []
+flag_words = ["todo"]
+
+# Downloads work fine here
+[download]
+"#;
+        let updated = update(content, |settings| {
+            settings.flag_words.clear();
+            settings.max_suggestions = Some(3);
+        });
+        assert_eq!(
+            updated,
+            r#"use_global = true
+words = []
+max_suggestions = 3
+
+# Downloads work fine here
+[download]
+"#
+        );
+    }
+
+    #[test]
+    fn test_keep_unknown_keys() {
+        let content = r#"words = ["apple"]
+future_setting = "yes"
+
+[download]
+offline = true
+future_download_setting = 1
+"#;
+        let updated = update(content, |settings| {
+            settings.words.push("pear".to_string());
+            settings.download.offline = None;
+        });
+        assert_eq!(
+            updated,
+            r#"words = ["apple", "pear"]
+future_setting = "yes"
+
+[download]
+future_download_setting = 1
+"#
+        );
+    }
+}
@@ -1,20 +1,38 @@
+mod directory;
+mod edit;
+pub mod schema;
 mod settings;
+mod validation;
+mod words;
+pub use crate::directory::DirectoryConfig;
+use crate::directory::{DEFAULT_DICTIONARY, rebase_path_patterns};
 use crate::settings::ConfigSettings;
+pub use crate::settings::{DownloadSettings, Mirror};
+pub use crate::validation::{ConfigIssue, validate_config};
+use crate::words::parse_word_list;
+use codebook_downloader::{Downloader, DownloaderOptions, UrlRewrite};
 use glob::Pattern;
 use log::debug;
+use log::error;
 use log::info;
 use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::io;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-use std::sync::RwLock;
-use std::time::SystemTime;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
 
 static CACHE_DIR: &str = "codebook";
 static GLOBAL_CONFIG_FILE: &str = "codebook.toml";
 static USER_CONFIG_FILES: [&str; 2] = ["codebook.toml", ".codebook.toml"];
+static OFFLINE_ENV: &str = "CODEBOOK_OFFLINE";
+static PROXY_ENV: &str = "CODEBOOK_PROXY";
+static CACHE_DIR_ENV: &str = "CODEBOOK_CACHE_DIR";
+/// How deep `extends` may nest, deeper files are most likely a mistake
+static MAX_EXTENDS_DEPTH: usize = 8;
 
 #[derive(Debug)]
 struct ConfigFileState {
@@ -30,8 +48,6 @@ pub struct CodebookConfig {
     global_settings: RwLock<Option<ConfigSettings>>,
     /// Combined settings (global merged with project overrides)
     effective_settings: RwLock<ConfigSettings>,
-    /// Compiled regex patterns for ignoring text
-    regex_cache: RwLock<Option<Vec<Regex>>>,
     /// Path to the project-specific config file
     pub project_config_path: Option<PathBuf>,
     project_config_state: RwLock<Option<ConfigFileState>>,
@@ -40,6 +56,15 @@ pub struct CodebookConfig {
     global_config_state: RwLock<Option<ConfigFileState>>,
     /// Directory for caching
     pub cache_dir: PathBuf,
+    /// Settings for each directory files were checked in, see `config_for_path`
+    directory_configs: RwLock<HashMap<PathBuf, Arc<DirectoryConfig>>>,
+    /// Config files looked for below the project root, with their state when
+    /// last read or None if they didn't exist
+    nested_config_states: RwLock<HashMap<PathBuf, Option<ConfigFileState>>>,
+    /// Files pulled in with `extends` or `word_lists`, with their state when last read
+    extended_config_states: RwLock<HashMap<PathBuf, Option<ConfigFileState>>>,
+    /// Fetches remote `extends` files, created when first needed
+    downloader: OnceLock<Option<Downloader>>,
 }
 
 impl Default for CodebookConfig {
@@ -48,12 +73,15 @@ impl Default for CodebookConfig {
             project_settings: RwLock::new(ConfigSettings::default()),
             global_settings: RwLock::new(None),
             effective_settings: RwLock::new(ConfigSettings::default()),
-            regex_cache: RwLock::new(None),
             project_config_path: None,
             project_config_state: RwLock::new(None),
             global_config_path: None,
             global_config_state: RwLock::new(None),
-            cache_dir: env::temp_dir().join(CACHE_DIR),
+            cache_dir: resolve_cache_dir(None, env::var(CACHE_DIR_ENV).ok()),
+            directory_configs: RwLock::new(HashMap::new()),
+            nested_config_states: RwLock::new(HashMap::new()),
+            extended_config_states: RwLock::new(HashMap::new()),
+            downloader: OnceLock::new(),
         }
     }
 }
@@ -143,6 +171,20 @@ impl CodebookConfig {
             config.project_config_path = Some(start_dir.join(USER_CONFIG_FILES[0]));
         }
 
+        // Changing the cache dir takes effect on restart. It is shared by all
+        // projects, so only the user's own global config may move it.
+        config.cache_dir = resolve_cache_dir(
+            config
+                .global_settings
+                .read()
+                .unwrap()
+                .as_ref()
+                .and_then(|global| global.cache_dir.as_deref()),
+            env::var(CACHE_DIR_ENV).ok(),
+        );
+        // Now that the cache dir is known, remote `extends` files can be fetched
+        config.recalculate_effective_settings();
+
         Ok(config)
     }
     /// Find the platform-specific global config directory and file path
@@ -281,6 +323,27 @@ impl CodebookConfig {
             }
         }
 
+        // Config files in sub-directories only affect the directory settings
+        let nested_changed = self
+            .nested_config_states
+            .read()
+            .unwrap()
+            .iter()
+            .any(|(path, state)| file_changed(path, state.as_ref()).unwrap_or(true));
+        if nested_changed {
+            self.nested_config_states.write().unwrap().clear();
+            changed = true;
+        }
+        let extended_changed = self
+            .extended_config_states
+            .read()
+            .unwrap()
+            .iter()
+            .any(|(path, state)| file_changed(path, state.as_ref()).unwrap_or(true));
+        if extended_changed {
+            changed = true;
+        }
+
         // Recalculate effective settings if anything changed
         if changed {
             self.recalculate_effective_settings();
@@ -313,102 +376,303 @@ impl CodebookConfig {
         path: &Path,
         state: &RwLock<Option<ConfigFileState>>,
     ) -> Result<bool, io::Error> {
-        match fs::metadata(path) {
-            Ok(metadata) => {
-                let current_modified = metadata.modified()?;
-                let current_size = metadata.len();
-
-                let state_guard = state.read().unwrap();
-                if let Some(cached_state) = &*state_guard {
-                    // Check if file has been modified or size has changed
-                    if current_modified > cached_state.last_modified
-                        || current_size != cached_state.last_size
-                    {
-                        return Ok(true);
-                    }
-                    return Ok(false);
-                }
-                // No cached state means we need to read the file
-                Ok(true)
-            }
-            Err(_) => {
-                // File doesn't exist or can't be accessed
-                // If we previously had a state, the file has changed (likely deleted)
-                Ok(state.read().unwrap().is_some())
-            }
-        }
+        file_changed(path, state.read().unwrap().as_ref())
     }
 
     /// Recalculate the effective settings based on global and project settings
     fn recalculate_effective_settings(&self) {
-        let mut effective = self.effective_settings.write().unwrap();
-        let project = self.project_settings.read().unwrap();
+        // Extended files are tracked again as they are resolved
+        self.extended_config_states.write().unwrap().clear();
+        let project = self.resolve_extends(
+            &self.project_settings.read().unwrap(),
+            self.project_config_path.as_deref(),
+        );
+        let global = self
+            .global_settings
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|global| self.resolve_extends(global, self.global_config_path.as_deref()));
 
-        if project.use_global {
-            if let Some(global) = self.global_settings.read().unwrap().as_ref() {
-                let mut new_effective = global.clone();
-                new_effective.merge(project.clone());
-                *effective = new_effective;
-            } else {
-                *effective = project.clone();
+        let mut effective = self.effective_settings.write().unwrap();
+        match global {
+            Some(mut global) if project.use_global => {
+                global.merge(project);
+                *effective = global;
             }
+            _ => *effective = project,
+        }
+
+        // Invalidate the per-directory settings
+        self.directory_configs.write().unwrap().clear();
+    }
+
+    /// Get the settings in effect for a file, like `config_for_path`, with the
+    /// `[[overrides]]` matching its path or any of its language IDs applied
+    pub fn config_for_file(
+        &self,
+        path: Option<&Path>,
+        language_ids: &[&str],
+    ) -> Arc<DirectoryConfig> {
+        let config = self.config_for_path(path);
+        config.with_overrides(path, language_ids).unwrap_or(config)
+    }
+
+    /// Get the settings in effect for a file. Config files in the directories
+    /// between the file and the project root, up to a repository root, are
+    /// merged over the project settings, the innermost taking precedence.
+    /// Settings are cached per directory until a config file changes.
+    pub fn config_for_path(&self, path: Option<&Path>) -> Arc<DirectoryConfig> {
+        let root = self
+            .project_config_path
+            .as_ref()
+            .and_then(|p| p.parent())
+            .map(Path::to_path_buf);
+        // Only configs loaded from disk look for further config files
+        let dir = match (path, &root) {
+            (Some(path), Some(root)) => root.join(path).parent().map(Path::to_path_buf),
+            _ => None,
+        };
+        let key = dir.clone().unwrap_or_default();
+        if let Some(config) = self.directory_configs.read().unwrap().get(&key) {
+            return config.clone();
+        }
+
+        let nested = match &dir {
+            Some(dir) => self.find_nested_configs(dir, root.as_deref()),
+            None => Vec::new(),
+        };
+        // Any config file can opt out of the global config
+        let mut settings = if nested.iter().all(|(_, s)| s.use_global) {
+            self.effective_settings.read().unwrap().clone()
         } else {
-            *effective = project.clone();
+            self.project_settings.read().unwrap().clone()
+        };
+        for (config_dir, nested_settings) in nested {
+            let config_path = config_dir.join(USER_CONFIG_FILES[0]);
+            let mut nested_settings = self.resolve_extends(&nested_settings, Some(&config_path));
+            rebase_path_patterns(&mut nested_settings, &config_dir, root.as_deref());
+            settings.merge(nested_settings);
         }
 
-        // Invalidate regex cache
-        *self.regex_cache.write().unwrap() = None;
+        let config = Arc::new(DirectoryConfig::new(settings, root));
+        self.directory_configs
+            .write()
+            .unwrap()
+            .insert(key, config.clone());
+        config
     }
 
-    /// Add a word to the project configs allowlist
-    pub fn add_word(&self, word: &str) -> Result<bool, io::Error> {
-        {
-            let word = word.to_ascii_lowercase();
-            let mut project_settings = self.project_settings.write().unwrap();
+    /// Merge the files listed in `extends` under the settings of the config
+    /// file at `path`, the file's own settings taking precedence. Files extend
+    /// others in turn; cycles and missing files are logged and skipped.
+    fn resolve_extends(&self, settings: &ConfigSettings, path: Option<&Path>) -> ConfigSettings {
+        let mut chain = HashSet::new();
+        self.resolve_extends_from(settings, path.and_then(Path::parent), &mut chain)
+    }
 
-            // Check if word already exists
-            if project_settings.words.contains(&word) {
-                return Ok(false);
+    fn resolve_extends_from(
+        &self,
+        settings: &ConfigSettings,
+        base_dir: Option<&Path>,
+        chain: &mut HashSet<PathBuf>,
+    ) -> ConfigSettings {
+        let mut resolved: Option<ConfigSettings> = None;
+        for source in &settings.extends {
+            let Some((path, is_remote)) = self.find_extended_config(source, base_dir) else {
+                continue;
+            };
+            if chain.len() >= MAX_EXTENDS_DEPTH || chain.contains(&path) {
+                error!(
+                    "Config extends itself or nests too deep, skipping: {}",
+                    source
+                );
+                continue;
+            }
+            let extended = match Self::load_settings_from_file(&path) {
+                Ok(extended) => extended,
+                Err(e) => {
+                    error!("Failed to load extended config {}: {}", source, e);
+                    continue;
+                }
+            };
+            // Relative paths in remote files have nothing to be relative to
+            let extended_dir = if is_remote { None } else { path.parent() };
+            chain.insert(path.clone());
+            let extended = self.resolve_extends_from(&extended, extended_dir, chain);
+            chain.remove(&path);
+            match resolved.as_mut() {
+                Some(resolved) => resolved.merge(extended),
+                None => resolved = Some(extended),
             }
+        }
 
-            // Add the word
-            project_settings.words.push(word);
-            // Sort/dedup for consistency
-            project_settings.words.sort();
-            project_settings.words.dedup();
+        let mut resolved = match resolved {
+            Some(mut resolved) => {
+                resolved.merge(settings.clone());
+                resolved.use_global = settings.use_global;
+                resolved.extends = settings.extends.clone();
+                resolved.word_lists = settings.word_lists.clone();
+                resolved
+            }
+            None => settings.clone(),
+        };
+        if !settings.word_lists.is_empty() {
+            resolved
+                .words
+                .extend(self.load_word_lists(&settings.word_lists, base_dir));
+            resolved.sort_and_dedup();
         }
-        self.recalculate_effective_settings();
+        resolved
+    }
 
-        Ok(true)
+    /// Read the words of `word_lists` files, relative to `base_dir`
+    fn load_word_lists(&self, word_lists: &[String], base_dir: Option<&Path>) -> Vec<String> {
+        let mut words = Vec::new();
+        for source in word_lists {
+            let Some(path) = resolve_local_path(source, base_dir) else {
+                error!("Can't resolve relative word list path: {}", source);
+                continue;
+            };
+            self.extended_config_states
+                .write()
+                .unwrap()
+                .insert(path.clone(), file_state(&path));
+            match fs::read_to_string(&path) {
+                Ok(content) => words.extend(parse_word_list(&content)),
+                Err(e) => error!("Failed to read word list {}: {}", path.display(), e),
+            }
+        }
+        words
     }
-    /// Add a word to the global configs allowlist
-    pub fn add_word_global(&self, word: &str) -> Result<bool, io::Error> {
-        {
-            let word = word.to_ascii_lowercase();
-            let mut global_settings = self.global_settings.write().unwrap();
 
-            let global_config = match global_settings.as_mut() {
-                Some(config) => config,
+    /// Find the local file for an `extends` entry, downloading URLs. Returns the
+    /// path and whether it came from a URL.
+    fn find_extended_config(
+        &self,
+        source: &str,
+        base_dir: Option<&Path>,
+    ) -> Option<(PathBuf, bool)> {
+        let is_remote = source.starts_with("https://") || source.starts_with("http://");
+        let path = if is_remote {
+            self.download_extended_config(source)?
+        } else {
+            match resolve_local_path(source, base_dir) {
+                Some(path) => path,
                 None => {
-                    *global_settings = Some(ConfigSettings::default());
-                    global_settings.as_mut().unwrap()
+                    error!("Can't resolve relative extends path: {}", source);
+                    return None;
                 }
-            };
+            }
+        };
+        let state = file_state(&path);
+        let exists = state.is_some();
+        self.extended_config_states
+            .write()
+            .unwrap()
+            .insert(path.clone(), state);
+        if !exists {
+            error!("Extended config not found: {}", path.display());
+            return None;
+        }
+        Some((path, is_remote))
+    }
 
-            // Check if word already exists
-            if global_config.words.contains(&word) {
-                return Ok(false);
+    fn download_extended_config(&self, url: &str) -> Option<PathBuf> {
+        // The downloader blocks, which isn't allowed on the threads of an async
+        // runtime, so use a thread of its own
+        let result = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let downloader = self.downloader.get_or_init(|| {
+                        Downloader::with_options(&self.cache_dir, self.get_downloader_options())
+                            .inspect_err(|e| error!("Failed to create downloader: {}", e))
+                            .ok()
+                    });
+                    downloader.as_ref().map(|d| d.get(url))
+                })
+                .join()
+        });
+        match result {
+            Ok(Some(Ok(path))) => Some(path),
+            Ok(Some(Err(e))) => {
+                error!("Failed to download extended config {}: {}", url, e);
+                None
             }
+            _ => None,
+        }
+    }
 
-            // Add the word
-            global_config.words.push(word);
-            // Sort/dedup for consistency
-            global_config.words.sort();
-            global_config.words.dedup();
+    /// Find config files from `dir` up to the project root (exclusive) or the
+    /// root of the repository, outermost first.
+    fn find_nested_configs(
+        &self,
+        dir: &Path,
+        root: Option<&Path>,
+    ) -> Vec<(PathBuf, ConfigSettings)> {
+        let mut found = Vec::new();
+        let mut states = self.nested_config_states.write().unwrap();
+        for current in dir.ancestors() {
+            if Some(current) == root {
+                break;
+            }
+            for config_name in USER_CONFIG_FILES {
+                let config_path = current.join(config_name);
+                let state = file_state(&config_path);
+                let exists = state.is_some();
+                states.insert(config_path.clone(), state);
+                if !exists {
+                    continue;
+                }
+                match Self::load_settings_from_file(&config_path) {
+                    Ok(settings) => {
+                        debug!("Loaded nested config from {}", config_path.display());
+                        found.push((current.to_path_buf(), settings));
+                    }
+                    Err(e) => error!("Failed to load config: {}", e),
+                }
+                // Like the project config, only the first file name found counts
+                break;
+            }
+            if current.join(".git").exists() {
+                break;
+            }
         }
-        self.recalculate_effective_settings();
+        found.reverse();
+        found
+    }
 
-        Ok(true)
+    /// Add a word to the project configs allowlist
+    pub fn add_word(&self, word: &str) -> Result<bool, io::Error> {
+        Ok(!self.add_words(&[word])?.is_empty())
+    }
+    /// Add a word to the global configs allowlist
+    pub fn add_word_global(&self, word: &str) -> Result<bool, io::Error> {
+        Ok(!self.add_words_global(&[word])?.is_empty())
+    }
+
+    /// Add words to the project configs allowlist at once, returning the ones
+    /// that weren't in it yet
+    pub fn add_words(&self, words: &[&str]) -> Result<Vec<String>, io::Error> {
+        let added = insert_words(&mut self.project_settings.write().unwrap().words, words);
+        if !added.is_empty() {
+            self.recalculate_effective_settings();
+        }
+        Ok(added)
+    }
+
+    /// Add words to the global configs allowlist at once, returning the ones
+    /// that weren't in it yet
+    pub fn add_words_global(&self, words: &[&str]) -> Result<Vec<String>, io::Error> {
+        let added = {
+            let mut global_settings = self.global_settings.write().unwrap();
+            let global_config = global_settings.get_or_insert_with(ConfigSettings::default);
+            insert_words(&mut global_config.words, words)
+        };
+        if !added.is_empty() {
+            self.recalculate_effective_settings();
+        }
+        Ok(added)
     }
 
     /// Add a file to the ignore list
@@ -432,6 +696,117 @@ impl CodebookConfig {
         Ok(true)
     }
 
+    /// Add a file to the ignore list, relative to the project root when inside it
+    pub fn add_ignore_file(&self, path: &Path) -> Result<bool, io::Error> {
+        let root = self.project_config_path.as_ref().and_then(|p| p.parent());
+        let path = root
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path);
+        // Paths may hold characters special to globs, like `[`
+        self.add_ignore(&Pattern::escape(&path.to_string_lossy()))
+    }
+
+    /// Remove a word from the project configs allowlist
+    pub fn remove_word(&self, word: &str) -> Result<bool, io::Error> {
+        {
+            let word = word.to_ascii_lowercase();
+            let mut project_settings = self.project_settings.write().unwrap();
+            if !project_settings.words.contains(&word) {
+                return Ok(false);
+            }
+            project_settings.words.retain(|w| *w != word);
+        }
+        self.recalculate_effective_settings();
+
+        Ok(true)
+    }
+
+    /// Remove a word from the global configs allowlist
+    pub fn remove_word_global(&self, word: &str) -> Result<bool, io::Error> {
+        {
+            let word = word.to_ascii_lowercase();
+            let mut global_settings = self.global_settings.write().unwrap();
+            let Some(global_config) = global_settings.as_mut() else {
+                return Ok(false);
+            };
+            if !global_config.words.contains(&word) {
+                return Ok(false);
+            }
+            global_config.words.retain(|w| *w != word);
+        }
+        self.recalculate_effective_settings();
+
+        Ok(true)
+    }
+
+    /// Check if a word was added to the project or global allowlist as is,
+    /// rather than matched by a pattern, so it can be removed again
+    pub fn is_added_word(&self, word: &str) -> bool {
+        let word = word.to_ascii_lowercase();
+        self.project_settings.read().unwrap().words.contains(&word)
+            || self
+                .global_settings
+                .read()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|settings| settings.words.contains(&word))
+    }
+
+    /// Add a word to the project configs list of words to always flag
+    pub fn flag_word(&self, word: &str) -> Result<bool, io::Error> {
+        {
+            let word = word.to_ascii_lowercase();
+            let mut project_settings = self.project_settings.write().unwrap();
+            if project_settings.flag_words.contains(&word) {
+                return Ok(false);
+            }
+            project_settings.flag_words.push(word);
+            project_settings.flag_words.sort();
+            project_settings.flag_words.dedup();
+        }
+        self.recalculate_effective_settings();
+
+        Ok(true)
+    }
+
+    /// Paths of the word lists of the project config, which words can be added to
+    pub fn get_word_list_paths(&self) -> Vec<PathBuf> {
+        let root = self.project_config_path.as_ref().and_then(|p| p.parent());
+        self.project_settings
+            .read()
+            .unwrap()
+            .word_lists
+            .iter()
+            .filter_map(|source| resolve_local_path(source, root))
+            .collect()
+    }
+
+    /// Append a word to a word list file, creating the file if needed
+    pub fn add_word_to_list(&self, word: &str, path: &Path) -> Result<bool, io::Error> {
+        let word = word.to_ascii_lowercase();
+        let mut content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        if parse_word_list(&content).contains(&word) {
+            return Ok(false);
+        }
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&word);
+        content.push('\n');
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        info!("Adding '{}' to word list {}", word, path.display());
+        fs::write(path, content)?;
+        self.recalculate_effective_settings();
+
+        Ok(true)
+    }
+
     /// Save the project configuration to its file
     pub fn save(&self) -> Result<(), io::Error> {
         let project_config_path = match self.project_config_path.as_ref() {
@@ -439,8 +814,8 @@ impl CodebookConfig {
             None => return Ok(()),
         };
 
-        let content = toml::to_string_pretty(&*self.project_settings.read().unwrap())
-            .map_err(io::Error::other)?;
+        let content =
+            settings_file_content(project_config_path, &self.project_settings.read().unwrap())?;
         info!(
             "Saving project configuration to {}",
             project_config_path.display()
@@ -455,8 +830,10 @@ impl CodebookConfig {
             None => return Ok(()),
         };
 
-        let content = toml::to_string_pretty(&*self.global_settings.read().unwrap())
-            .map_err(io::Error::other)?;
+        let content = match self.global_settings.read().unwrap().as_ref() {
+            Some(settings) => settings_file_content(global_config_path, settings)?,
+            None => return Ok(()),
+        };
         info!(
             "Saving global configuration to {}",
             global_config_path.display()
@@ -472,68 +849,133 @@ impl CodebookConfig {
     pub fn get_dictionary_ids(&self) -> Vec<String> {
         let ids = self.effective_settings.read().unwrap().dictionaries.clone();
         if ids.is_empty() {
-            return vec!["en_us".to_string()];
+            return vec![DEFAULT_DICTIONARY.to_string()];
         }
         ids
     }
 
-    /// Check if a path should be ignored based on the effective configuration
-    pub fn should_ignore_path<P: AsRef<Path>>(&self, path: P) -> bool {
-        let path_str = path.as_ref().to_string_lossy();
-        self.effective_settings
+    /// Get dictionary IDs to use for a file. Dictionaries configured for patterns
+    /// matching the path replace the top-level `dictionaries`.
+    pub fn get_dictionary_ids_for_path<P: AsRef<Path>>(&self, path: P) -> Vec<String> {
+        self.config_for_path(None).get_dictionary_ids_for_path(path)
+    }
+
+    /// Get every dictionary ID the configuration refers to, including those only
+    /// used for some paths
+    pub fn get_all_dictionary_ids(&self) -> Vec<String> {
+        let mut ids = self.get_dictionary_ids();
+        ids.extend(
+            self.effective_settings
+                .read()
+                .unwrap()
+                .path_dictionaries
+                .iter()
+                .flat_map(|p| p.dictionaries.clone()),
+        );
+        ids.extend(
+            self.effective_settings
+                .read()
+                .unwrap()
+                .overrides
+                .iter()
+                .flat_map(|o| o.dictionaries.clone()),
+        );
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    /// Check whether each region of a file should be checked against the
+    /// dictionary that fits it best, rather than all configured dictionaries
+    pub fn should_detect_language(&self) -> bool {
+        self.config_for_path(None).should_detect_language()
+    }
+
+    /// Get the maximum number of suggestions to offer for a misspelled word
+    pub fn get_max_suggestions(&self) -> usize {
+        self.config_for_path(None).get_max_suggestions()
+    }
+
+    /// Get dictionary download settings from the effective configuration.
+    /// `proxy` and `mirrors` are only read from the global config file, as
+    /// downloads land in the cache shared by all projects. The `CODEBOOK_OFFLINE`
+    /// and `CODEBOOK_PROXY` environment variables take precedence.
+    pub fn get_download_settings(&self) -> DownloadSettings {
+        let mut settings = self.effective_settings.read().unwrap().download.clone();
+        let global = self
+            .global_settings
             .read()
             .unwrap()
-            .ignore_paths
-            .iter()
-            .any(|pattern| {
-                Pattern::new(pattern)
-                    .map(|p| p.matches(&path_str))
-                    .unwrap_or(false)
-            })
+            .as_ref()
+            .map(|global| global.download.clone())
+            .unwrap_or_default();
+        settings.proxy = global.proxy;
+        settings.mirrors = global.mirrors;
+        apply_download_env(
+            settings,
+            env::var(OFFLINE_ENV).ok(),
+            env::var(PROXY_ENV).ok(),
+        )
+    }
+
+    /// Get options for downloading dictionaries, from the download settings
+    pub fn get_downloader_options(&self) -> DownloaderOptions {
+        let settings = self.get_download_settings();
+        let defaults = DownloaderOptions::default();
+        DownloaderOptions {
+            url_rewrites: settings
+                .mirrors
+                .into_iter()
+                .map(|mirror| UrlRewrite {
+                    from: mirror.from,
+                    to: mirror.to,
+                })
+                .collect(),
+            proxy: settings.proxy,
+            connect_timeout: settings
+                .connect_timeout
+                .map_or(defaults.connect_timeout, Duration::from_secs),
+            read_timeout: settings
+                .read_timeout
+                .map_or(defaults.read_timeout, Duration::from_secs),
+            refresh_interval: settings
+                .refresh_days
+                .map_or(defaults.refresh_interval, |days| {
+                    Duration::from_secs(days * 24 * 3600)
+                }),
+            offline: settings.offline.unwrap_or(defaults.offline),
+        }
+    }
+
+    /// Check if a path should be ignored based on the effective configuration.
+    /// Relative patterns match paths relative to the project root.
+    pub fn should_ignore_path<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.config_for_path(None).should_ignore_path(path)
     }
 
     /// Check if a word is in the effective allowlist
     pub fn is_allowed_word(&self, word: &str) -> bool {
-        let word = word.to_ascii_lowercase();
-        self.effective_settings
-            .read()
-            .unwrap()
-            .words
-            .iter()
-            .any(|w| w == &word)
+        self.config_for_path(None).is_allowed_word(word)
     }
 
     /// Check if a word should be flagged according to effective configuration
     pub fn should_flag_word(&self, word: &str) -> bool {
-        let word = word.to_ascii_lowercase();
-        self.effective_settings
-            .read()
-            .unwrap()
-            .flag_words
-            .iter()
-            .any(|w| w == &word)
+        self.config_for_path(None).should_flag_word(word)
     }
 
-    /// Get the list of user-defined ignore patterns
-    pub fn get_ignore_patterns(&self) -> Option<Vec<Regex>> {
-        let str_patterns = self
-            .effective_settings
-            .read()
-            .unwrap()
-            .ignore_patterns
-            .clone();
-
-        // Lazily initialize the Regex cache
-        let mut regex_cache = self.regex_cache.write().unwrap();
-        if regex_cache.is_none() {
-            let regex_set = str_patterns
-                .into_iter()
-                .map(|pattern| Regex::new(&pattern).unwrap())
-                .collect::<Vec<_>>();
-            *regex_cache = Some(regex_set);
-        }
+    /// Check whether a file is a config file Codebook reads: the global config
+    /// or a project or directory config file
+    pub fn is_config_file(&self, path: &Path) -> bool {
+        self.global_config_path.as_deref() == Some(path)
+            || path
+                .file_name()
+                .is_some_and(|name| USER_CONFIG_FILES.iter().any(|file| name == *file))
+    }
 
-        regex_cache.clone()
+    /// Get the list of user-defined ignore patterns, invalid ones are left out.
+    /// Also includes patterns matching the case-sensitive words as written.
+    pub fn get_ignore_patterns(&self) -> Vec<Regex> {
+        self.config_for_path(None).get_ignore_patterns().to_vec()
     }
 
     /// Clean the cache directory
@@ -544,13 +986,11 @@ impl CodebookConfig {
             return;
         }
 
-        // Safety check: Ensure CACHE_DIR is in the path
-        let path_str = dir_path.to_string_lossy();
-        if !path_str.contains(CACHE_DIR) {
+        // Safety check: the cache dir can be configured, so make sure it is ours
+        if !is_cache_dir(&dir_path) {
             log::error!(
-                "Cache directory path '{}' doesn't contain '{}', refusing to clean",
-                path_str,
-                CACHE_DIR
+                "'{}' doesn't look like a Codebook cache directory, refusing to clean",
+                dir_path.display()
             );
             return;
         }
@@ -572,9 +1012,140 @@ impl CodebookConfig {
     }
 }
 
+/// Content of a config file holding `settings`, edited in place if the file exists
+fn settings_file_content(path: &Path, settings: &ConfigSettings) -> Result<String, io::Error> {
+    match fs::read_to_string(path) {
+        Ok(content) => edit::update_document(&content, settings),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            toml::to_string_pretty(settings).map_err(io::Error::other)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Resolve a local path from a config file, relative paths against `base_dir`
+fn resolve_local_path(source: &str, base_dir: Option<&Path>) -> Option<PathBuf> {
+    let path = expand_home(source);
+    match base_dir {
+        _ if path.is_absolute() => Some(path),
+        Some(base_dir) => Some(base_dir.join(path)),
+        None => None,
+    }
+}
+
+/// The cache dir: a `codebook` directory in `CODEBOOK_CACHE_DIR`, the global
+/// config's `cache_dir` or the platform's cache directory (`$XDG_CACHE_HOME` or
+/// `~/.cache` on Unix), in that order.
+fn resolve_cache_dir(configured: Option<&str>, env_value: Option<String>) -> PathBuf {
+    // Files are kept in a directory of our own, the cache is cleaned up
+    if let Some(dir) = env_value
+        .as_deref()
+        .filter(|d| !d.is_empty())
+        .or(configured)
+    {
+        return expand_home(dir).join(CACHE_DIR);
+    }
+    let platform_dir = if cfg!(unix) {
+        env::var("XDG_CACHE_HOME")
+            .ok()
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|home| home.join(".cache")))
+    } else {
+        dirs::cache_dir()
+    };
+    platform_dir.unwrap_or_else(env::temp_dir).join(CACHE_DIR)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    let rest = match path {
+        "~" => Some(""),
+        _ => path.strip_prefix("~/"),
+    };
+    match (rest, dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Whether a directory is a Codebook cache, and not e.g. a misconfigured home
+/// directory, so it is safe to empty.
+fn is_cache_dir(dir: &Path) -> bool {
+    if dir.parent().is_none() || dirs::home_dir().is_some_and(|home| home == dir) {
+        return false;
+    }
+    dir.file_name().is_some_and(|name| name == CACHE_DIR)
+}
+
+/// The state of a file on disk, None if it doesn't exist
+fn file_state(path: &Path) -> Option<ConfigFileState> {
+    let metadata = fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some(ConfigFileState {
+        last_modified: metadata.modified().ok()?,
+        last_size: metadata.len(),
+    })
+}
+
+/// Check if a file has changed since it was in `state`
+fn file_changed(path: &Path, state: Option<&ConfigFileState>) -> Result<bool, io::Error> {
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            let current_modified = metadata.modified()?;
+            let current_size = metadata.len();
+
+            if let Some(cached_state) = state {
+                // Check if file has been modified or size has changed
+                return Ok(current_modified > cached_state.last_modified
+                    || current_size != cached_state.last_size);
+            }
+            // No cached state means we need to read the file
+            Ok(true)
+        }
+        Err(_) => {
+            // File doesn't exist or can't be accessed
+            // If we previously had a state, the file has changed (likely deleted)
+            Ok(state.is_some())
+        }
+    }
+}
+
+/// Add lowercased words missing from `list`, keeping it sorted, and return them
+fn insert_words(list: &mut Vec<String>, words: &[&str]) -> Vec<String> {
+    let mut added = Vec::new();
+    for word in words {
+        let word = word.to_ascii_lowercase();
+        if !list.contains(&word) && !added.contains(&word) {
+            added.push(word);
+        }
+    }
+    list.extend(added.iter().cloned());
+    // Sort/dedup for consistency
+    list.sort();
+    list.dedup();
+    added
+}
+
+fn apply_download_env(
+    mut settings: DownloadSettings,
+    offline: Option<String>,
+    proxy: Option<String>,
+) -> DownloadSettings {
+    if let Some(offline) = offline {
+        settings.offline = Some(matches!(
+            offline.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes"
+        ));
+    }
+    if let Some(proxy) = proxy.filter(|p| !p.is_empty()) {
+        settings.proxy = Some(proxy);
+    }
+    settings
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::directory::DEFAULT_MAX_SUGGESTIONS;
     use std::fs::File;
     use std::io::Write;
     use tempfile::TempDir;
@@ -612,6 +1183,340 @@ mod tests {
         Ok(config)
     }
 
+    #[test]
+    fn test_download_env_overrides() {
+        let settings = DownloadSettings {
+            offline: Some(false),
+            proxy: Some("http://config-proxy".to_string()),
+            ..Default::default()
+        };
+        let overridden = apply_download_env(
+            settings.clone(),
+            Some("1".to_string()),
+            Some("http://env-proxy".to_string()),
+        );
+        assert_eq!(overridden.offline, Some(true));
+        assert_eq!(overridden.proxy.as_deref(), Some("http://env-proxy"));
+
+        let overridden = apply_download_env(settings.clone(), Some("false".to_string()), None);
+        assert_eq!(overridden.offline, Some(false));
+        assert_eq!(overridden.proxy, settings.proxy);
+    }
+
+    #[test]
+    fn test_download_mirrors_only_from_global_config() {
+        let mirror = |from: &str| Mirror {
+            from: from.to_string(),
+            to: "https://mirror/".to_string(),
+        };
+        let config = CodebookConfig::default();
+        *config.project_settings.write().unwrap() = ConfigSettings {
+            download: DownloadSettings {
+                proxy: Some("http://project-proxy".to_string()),
+                mirrors: vec![mirror("https://project/")],
+                refresh_days: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        config.recalculate_effective_settings();
+        let settings = config.get_download_settings();
+        assert!(settings.mirrors.is_empty());
+        assert_eq!(settings.refresh_days, Some(3));
+        if env::var(PROXY_ENV).is_err() {
+            assert_eq!(settings.proxy, None);
+        }
+
+        *config.global_settings.write().unwrap() = Some(ConfigSettings {
+            download: DownloadSettings {
+                mirrors: vec![mirror("https://global/")],
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        config.recalculate_effective_settings();
+        assert_eq!(
+            config.get_download_settings().mirrors,
+            vec![mirror("https://global/")]
+        );
+    }
+
+    #[test]
+    fn test_dictionary_ids_for_path() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("codebook.toml");
+        let mut file = File::create(&config_path)?;
+        write!(
+            file,
+            r#"
+            dictionaries = ["en_us"]
+
+            [[path_dictionaries]]
+            paths = ["docs/de/**"]
+            dictionaries = ["de"]
+            "#
+        )?;
+
+        let config = load_from_file(ConfigType::Project, &config_path)?;
+        let german_doc = temp_dir.path().join("docs/de/index.md");
+        assert_eq!(config.get_dictionary_ids_for_path(&german_doc), vec!["de"]);
+        assert_eq!(
+            config.get_dictionary_ids_for_path("docs/de/index.md"),
+            vec!["de"]
+        );
+        let english_doc = temp_dir.path().join("docs/index.md");
+        assert_eq!(
+            config.get_dictionary_ids_for_path(&english_doc),
+            vec!["en_us"]
+        );
+        assert_eq!(config.get_all_dictionary_ids(), vec!["de", "en_us"]);
+        assert!(!config.should_detect_language());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_configs() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir(root.join(".git"))?;
+        fs::create_dir_all(root.join("packages/a/src"))?;
+        fs::create_dir_all(root.join("packages/b"))?;
+        fs::write(root.join("codebook.toml"), "words = [\"rootword\"]\n")?;
+        fs::write(
+            root.join("packages/a/codebook.toml"),
+            "words = [\"aword\"]\nignore_paths = [\"dist/**\"]\nmax_suggestions = 3\n",
+        )?;
+
+        let config = CodebookConfig::load(Some(root))?;
+        let a_file = root.join("packages/a/src/main.rs");
+        let a_config = config.config_for_path(Some(&a_file));
+        assert!(a_config.is_allowed_word("rootword"));
+        assert!(a_config.is_allowed_word("aword"));
+        assert_eq!(a_config.get_max_suggestions(), 3);
+        assert!(a_config.should_ignore_path(root.join("packages/a/dist/index.js")));
+        assert!(!a_config.should_ignore_path(root.join("packages/b/dist/index.js")));
+        // Relative paths are relative to the project root
+        assert!(
+            config
+                .config_for_path(Some(Path::new("packages/a/src/lib.rs")))
+                .is_allowed_word("aword")
+        );
+
+        let b_file = root.join("packages/b/main.rs");
+        let b_config = config.config_for_path(Some(&b_file));
+        assert!(b_config.is_allowed_word("rootword"));
+        assert!(!b_config.is_allowed_word("aword"));
+
+        // New config files are picked up on reload
+        fs::write(
+            root.join("packages/b/.codebook.toml"),
+            "words = [\"bword\"]\n",
+        )?;
+        assert!(config.reload()?);
+        assert!(
+            config
+                .config_for_path(Some(&b_file))
+                .is_allowed_word("bword")
+        );
+        assert!(!config.reload()?);
+
+        // Configs not loaded from disk don't look for config files
+        let default_config = CodebookConfig::default();
+        assert!(
+            !default_config
+                .config_for_path(Some(&a_file))
+                .is_allowed_word("aword")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_configs_stop_at_repository_root() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("repo");
+        fs::create_dir_all(repo.join(".git"))?;
+        fs::create_dir_all(repo.join("app"))?;
+        fs::write(
+            temp_dir.path().join("codebook.toml"),
+            "words = [\"outside\"]\n",
+        )?;
+        fs::write(repo.join("codebook.toml"), "words = [\"repoword\"]\n")?;
+
+        // The workspace is a sub-directory of the repository without its own config
+        let config = CodebookConfig::load(Some(&repo.join("app")))?;
+        let file_config = config.config_for_path(Some(&repo.join("app/main.rs")));
+        assert!(file_config.is_allowed_word("repoword"));
+        assert!(!file_config.is_allowed_word("outside"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extends() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let shared = temp_dir.path().join("shared");
+        let project = temp_dir.path().join("project");
+        fs::create_dir_all(&shared)?;
+        fs::create_dir_all(&project)?;
+        fs::write(
+            shared.join("base.toml"),
+            "words = [\"baseword\"]\nmax_suggestions = 3\nextends = [\"org.toml\"]\n",
+        )?;
+        fs::write(shared.join("org.toml"), "flag_words = [\"orgflag\"]\n")?;
+        fs::write(
+            project.join("codebook.toml"),
+            "extends = [\"../shared/base.toml\", \"missing.toml\"]\nwords = [\"localword\"]\nmax_suggestions = 7\n",
+        )?;
+
+        let config = CodebookConfig::load(Some(&project))?;
+        assert!(config.is_allowed_word("baseword"));
+        assert!(config.is_allowed_word("localword"));
+        assert!(config.should_flag_word("orgflag"));
+        // The extending file takes precedence
+        assert_eq!(config.get_max_suggestions(), 7);
+
+        // Extended settings aren't written into the project config
+        config.add_word("newword")?;
+        config.save()?;
+        let saved = fs::read_to_string(project.join("codebook.toml"))?;
+        assert!(!saved.contains("baseword"));
+        assert!(saved.contains("../shared/base.toml"));
+
+        // Changes to extended files are picked up on reload
+        fs::write(shared.join("org.toml"), "flag_words = [\"otherflag\"]\n")?;
+        assert!(config.reload()?);
+        assert!(config.should_flag_word("otherflag"));
+        assert!(!config.should_flag_word("orgflag"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extends_cycle() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(
+            root.join("codebook.toml"),
+            "extends = [\"other.toml\"]\nwords = [\"rootword\"]\n",
+        )?;
+        fs::write(
+            root.join("other.toml"),
+            "extends = [\"codebook.toml\", \"other.toml\"]\nwords = [\"otherword\"]\n",
+        )?;
+
+        let config = CodebookConfig::load(Some(root))?;
+        assert!(config.is_allowed_word("rootword"));
+        assert!(config.is_allowed_word("otherword"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_lists() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("codebook.toml"), "word_lists = [\"words.txt\"]\n")?;
+        fs::write(root.join("words.txt"), "# Team words\nTeamword")?;
+
+        let config = CodebookConfig::load(Some(root))?;
+        assert!(config.is_allowed_word("teamword"));
+        let paths = config.get_word_list_paths();
+        assert_eq!(paths, vec![root.join("words.txt")]);
+
+        assert!(config.add_word_to_list("Another", &paths[0])?);
+        assert!(!config.add_word_to_list("teamword", &paths[0])?);
+        assert!(config.is_allowed_word("another"));
+        assert_eq!(
+            fs::read_to_string(root.join("words.txt"))?,
+            "# Team words\nTeamword\nanother\n"
+        );
+        // Word lists aren't written into the config
+        config.save()?;
+        assert_eq!(
+            fs::read_to_string(root.join("codebook.toml"))?,
+            "word_lists = [\"words.txt\"]\n"
+        );
+
+        // Changes to word lists are picked up on reload
+        fs::write(root.join("words.txt"), "other\n")?;
+        assert!(config.reload()?);
+        assert!(config.is_allowed_word("other"));
+        assert!(!config.is_allowed_word("teamword"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_cache_dir() {
+        let configured = Some("/srv/cache");
+        assert_eq!(
+            resolve_cache_dir(configured, Some("/env/cache".to_string())),
+            PathBuf::from("/env/cache/codebook")
+        );
+        assert_eq!(
+            resolve_cache_dir(configured, Some(String::new())),
+            PathBuf::from("/srv/cache/codebook")
+        );
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(resolve_cache_dir(Some("~"), None), home.join(CACHE_DIR));
+        }
+        let default_dir = resolve_cache_dir(None, None);
+        assert!(default_dir.ends_with(CACHE_DIR));
+        assert_ne!(default_dir, env::temp_dir().join(CACHE_DIR));
+    }
+
+    #[test]
+    fn test_cache_dir_never_uses_project_dirs() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let documents = temp_dir.path().join("Documents");
+        fs::create_dir(&documents)?;
+        let thesis = documents.join("thesis.docx");
+        let old = std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 3600);
+        File::create(&thesis)?.set_modified(old)?;
+
+        // A project can't point the shared cache at the user's files
+        let project = temp_dir.path().join("project");
+        fs::create_dir(&project)?;
+        fs::write(
+            project.join("codebook.toml"),
+            format!("cache_dir = {:?}\n", documents.display().to_string()),
+        )?;
+        let config = CodebookConfig::load(Some(&project))?;
+        assert!(!config.cache_dir.starts_with(&documents));
+
+        // Configured directories only hold the cache directory
+        let cache_dir = resolve_cache_dir(Some(documents.to_str().unwrap()), None);
+        assert_eq!(cache_dir, documents.join(CACHE_DIR));
+        Downloader::new(&cache_dir).unwrap();
+        assert!(thesis.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_clean_cache_refuses_unknown_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let other = temp_dir.path().join("other");
+        fs::create_dir(&other).unwrap();
+        fs::write(other.join("keep.txt"), "keep").unwrap();
+        let config = CodebookConfig {
+            cache_dir: other.clone(),
+            ..Default::default()
+        };
+        config.clean_cache();
+        assert!(other.join("keep.txt").exists());
+
+        // Downloader metadata doesn't make a directory ours
+        fs::write(other.join("_metadata.json"), "{}").unwrap();
+        config.clean_cache();
+        assert!(other.join("keep.txt").exists());
+
+        let cache = temp_dir.path().join(CACHE_DIR);
+        fs::create_dir(&cache).unwrap();
+        fs::write(cache.join("file"), "").unwrap();
+        let config = CodebookConfig {
+            cache_dir: cache.clone(),
+            ..Default::default()
+        };
+        config.clean_cache();
+        assert!(!cache.join("file").exists());
+    }
+
     #[test]
     fn test_save_global_creates_directories() -> Result<(), io::Error> {
         let temp_dir = TempDir::new().unwrap();
@@ -661,6 +1566,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_save_keeps_formatting() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("codebook.toml");
+        fs::write(
+            &config_path,
+            "# Team words\nwords = [\"alpha\", \"gamma\"] # sorted\n\n[download]\noffline = true\n",
+        )?;
+        let config = load_from_file(ConfigType::Project, &config_path)?;
+        config.add_word("Beta")?;
+        config.save()?;
+        assert_eq!(
+            fs::read_to_string(&config_path)?,
+            "# Team words\nwords = [\"alpha\", \"beta\", \"gamma\"] # sorted\n\n[download]\noffline = true\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_patterns() -> Result<(), io::Error> {
+        let config = CodebookConfig::default();
+        assert!(!config.is_allowed_word("k8sCluster"));
+        // Compiled word lists are rebuilt when words are added
+        config.add_word("k8s*")?;
+        assert!(config.is_allowed_word("k8sCluster"));
+        assert!(!config.should_flag_word("k8sCluster"));
+        Ok(())
+    }
+
     #[test]
     fn test_add_word_global() -> Result<(), io::Error> {
         let temp_dir = TempDir::new().unwrap();
@@ -685,6 +1619,78 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_add_words() -> Result<(), io::Error> {
+        let config = CodebookConfig::default();
+        config.add_word("existing")?;
+        let added = config.add_words(&["Zeta", "existing", "alpha", "zeta"])?;
+        assert_eq!(added, vec!["zeta", "alpha"]);
+        assert_eq!(
+            config.project_settings.read().unwrap().words,
+            vec!["alpha", "existing", "zeta"]
+        );
+        assert!(config.is_allowed_word("alpha"));
+        assert!(config.add_words(&["alpha"])?.is_empty());
+
+        let added = config.add_words_global(&["globalword"])?;
+        assert_eq!(added, vec!["globalword"]);
+        assert!(config.is_allowed_word("globalword"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_and_flag_word() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("codebook.toml");
+        let config = CodebookConfig {
+            project_config_path: Some(config_path.clone()),
+            global_settings: RwLock::new(Some(ConfigSettings::default())),
+            ..Default::default()
+        };
+        config.add_word("Project")?;
+        config.add_word_global("global")?;
+        assert!(config.is_added_word("project"));
+        assert!(config.is_added_word("GLOBAL"));
+
+        assert!(config.remove_word("PROJECT")?);
+        assert!(!config.remove_word("global")?);
+        assert!(config.remove_word_global("global")?);
+        assert!(!config.is_allowed_word("project"));
+        assert!(!config.is_allowed_word("global"));
+
+        assert!(config.flag_word("Wrong")?);
+        assert!(!config.flag_word("wrong")?);
+        config.save()?;
+        let loaded_config = load_from_file(ConfigType::Project, &config_path)?;
+        assert!(loaded_config.should_flag_word("wrong"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_ignore_file() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let config = CodebookConfig {
+            project_config_path: Some(root.join("codebook.toml")),
+            ..Default::default()
+        };
+        let file = root.join("src/[id]/page.md");
+        assert!(config.add_ignore_file(&file)?);
+        assert!(!config.add_ignore_file(&file)?);
+        assert_eq!(
+            config.project_settings.read().unwrap().ignore_paths,
+            vec!["src/[[]id[]]/page.md"]
+        );
+        let file_config = config.config_for_file(Some(&file), &[]);
+        assert!(file_config.should_ignore_path(&file));
+        assert!(!file_config.should_ignore_path(root.join("src/i/page.md")));
+        // The project-wide check matches relative patterns the same way
+        assert!(config.should_ignore_path(&file));
+
+        Ok(())
+    }
+
     #[test]
     fn test_ignore_patterns() -> Result<(), io::Error> {
         let temp_dir = TempDir::new().unwrap();
@@ -707,10 +1713,7 @@ mod tests {
             .clone();
         assert!(patterns.contains(&String::from("^[ATCG]+$")));
         assert!(patterns.contains(&String::from("\\d{3}-\\d{2}-\\d{4}")));
-        let reg = config.get_ignore_patterns();
-
-        let patterns = reg.as_ref().unwrap();
-        assert!(patterns.len() == 2);
+        assert!(config.get_ignore_patterns().len() == 2);
         Ok(())
     }
 
@@ -731,7 +1734,7 @@ mod tests {
         )?;
 
         let config = load_from_file(ConfigType::Project, &config_path)?;
-        assert!(config.get_ignore_patterns().unwrap().len() == 1);
+        assert!(config.get_ignore_patterns().len() == 1);
 
         // Update config with new pattern
         let mut file = File::create(&config_path)?;
@@ -745,7 +1748,7 @@ mod tests {
 
         // Reload and verify both patterns work
         config.reload()?;
-        assert!(config.get_ignore_patterns().unwrap().len() == 2);
+        assert!(config.get_ignore_patterns().len() == 2);
 
         // Update config to remove all patterns
         let mut file = File::create(&config_path)?;
@@ -758,7 +1761,7 @@ mod tests {
 
         // Reload and verify no patterns match
         config.reload()?;
-        assert!(config.get_ignore_patterns().unwrap().is_empty());
+        assert!(config.get_ignore_patterns().is_empty());
 
         Ok(())
     }
@@ -797,6 +1800,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_max_suggestions() -> Result<(), io::Error> {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("codebook.toml");
+
+        let config = CodebookConfig::default();
+        assert_eq!(config.get_max_suggestions(), DEFAULT_MAX_SUGGESTIONS);
+
+        let mut file = File::create(&config_path)?;
+        write!(
+            file,
+            r#"
+            max_suggestions = 8
+            "#
+        )?;
+        let config = load_from_file(ConfigType::Project, &config_path)?;
+        assert_eq!(config.get_max_suggestions(), 8);
+        Ok(())
+    }
+
     #[test]
     fn test_should_ignore_path() {
         let config = CodebookConfig::default();
@@ -0,0 +1,397 @@
+use crate::settings::ConfigSettings;
+use schemars::generate::SchemaSettings;
+use serde_json::{Value, json};
+use std::sync::LazyLock;
+
+/// A key of a config file, with what it holds and what it does
+#[derive(Debug, PartialEq)]
+pub struct SettingInfo {
+    pub key: String,
+    pub kind: SettingKind,
+    pub description: String,
+    /// Only read from the global config, like the cache and download locations
+    pub global_only: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SettingKind {
+    Bool,
+    Integer,
+    String,
+    Strings,
+    /// List of dictionary IDs
+    Dictionaries,
+    Table(Vec<SettingInfo>),
+    /// Array of tables, like `[[overrides]]`
+    Tables(Vec<SettingInfo>),
+}
+
+/// Settings of a config file, from `ConfigSettings` and the doc comments of its fields
+pub static SETTINGS: LazyLock<Vec<SettingInfo>> =
+    LazyLock::new(|| table_settings(&settings_schema()));
+
+/// JSON Schema of `ConfigSettings`, with the schemas of the tables it holds inlined
+fn settings_schema() -> Value {
+    SchemaSettings::draft07()
+        .with(|settings| settings.inline_subschemas = true)
+        .into_generator()
+        .into_root_schema_for::<ConfigSettings>()
+        .to_value()
+}
+
+fn table_settings(schema: &Value) -> Vec<SettingInfo> {
+    schema["properties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(key, property)| SettingInfo {
+            key: key.clone(),
+            kind: setting_kind(property),
+            // Doc comments are wrapped
+            description: property["description"]
+                .as_str()
+                .unwrap_or_default()
+                .replace('\n', " "),
+            global_only: property.get(GLOBAL_ONLY_EXTENSION).is_some(),
+        })
+        .collect()
+}
+
+fn setting_kind(property: &Value) -> SettingKind {
+    if property.get(DICTIONARIES_EXTENSION).is_some() {
+        return SettingKind::Dictionaries;
+    }
+    // Optional settings are also typed as null
+    let kind = match &property["type"] {
+        Value::Array(types) => types.iter().find(|t| *t != "null"),
+        kind => Some(kind),
+    };
+    match kind.and_then(Value::as_str) {
+        Some("boolean") => SettingKind::Bool,
+        Some("integer") => SettingKind::Integer,
+        Some("object") => SettingKind::Table(table_settings(property)),
+        Some("array") if property["items"]["type"] == "object" => {
+            SettingKind::Tables(table_settings(&property["items"]))
+        }
+        Some("array") => SettingKind::Strings,
+        _ => SettingKind::String,
+    }
+}
+
+/// Marks lists of dictionary IDs in the schema of `ConfigSettings`
+const DICTIONARIES_EXTENSION: &str = "x-dictionaries";
+/// Marks settings only read from the global config
+const GLOBAL_ONLY_EXTENSION: &str = "x-global-only";
+
+/// Find the setting at a key path like `["download", "mirrors", "from"]`
+pub fn find_setting(path: &[&str]) -> Option<&'static SettingInfo> {
+    let (last, parents) = path.split_last()?;
+    settings_in(parents)?.iter().find(|s| s.key == *last)
+}
+
+/// Find the settings of the table at a key path, `SETTINGS` for an empty path
+pub fn settings_in(path: &[&str]) -> Option<&'static [SettingInfo]> {
+    path.iter().try_fold(SETTINGS.as_slice(), |settings, key| {
+        match &settings.iter().find(|s| s.key == *key)?.kind {
+            SettingKind::Table(settings) | SettingKind::Tables(settings) => {
+                Some(settings.as_slice())
+            }
+            _ => None,
+        }
+    })
+}
+
+/// What can be completed at a position in a config file
+#[derive(Debug, PartialEq)]
+pub enum ConfigCompletion {
+    /// Keys of the table the position is in
+    Keys(&'static [SettingInfo]),
+    /// Dictionary IDs, `quoted` if the position is inside a string already
+    Dictionaries {
+        quoted: bool,
+    },
+    None,
+}
+
+/// Find what can be completed at a zero-based line and character
+pub fn completion_at(content: &str, line: u32, character: u32) -> ConfigCompletion {
+    let lines: Vec<&str> = content.lines().collect();
+    let line = line as usize;
+    let current = lines.get(line).copied().unwrap_or_default();
+    let prefix: String = current.chars().take(character as usize).collect();
+    if prefix.trim_start().starts_with('[') || prefix.trim_start().starts_with('#') {
+        return ConfigCompletion::None;
+    }
+    let table = table_path(&lines[..line.min(lines.len())]);
+
+    // The value of a key on this line, or of an array opened on an earlier one
+    let value = match prefix.split_once('=') {
+        Some((key, value)) => Some((key.to_string(), value.to_string())),
+        None => {
+            let previous = &lines[..line.min(lines.len())];
+            previous
+                .iter()
+                .rposition(|l| l.contains('=') || l.trim_start().starts_with('['))
+                .and_then(|start| {
+                    let (key, value) = previous[start].split_once('=')?;
+                    let mut value = value.to_string();
+                    for l in previous[start + 1..].iter().chain([&prefix.as_str()]) {
+                        value.push('\n');
+                        value.push_str(l);
+                    }
+                    Some((key.to_string(), value))
+                })
+                .filter(|(_, value)| value.matches('[').count() > value.matches(']').count())
+        }
+    };
+    if let Some((key, value)) = value {
+        let mut path = table.clone();
+        path.extend(key.split('.').map(str::trim));
+        return match find_setting(&path) {
+            Some(SettingInfo {
+                kind: SettingKind::Dictionaries,
+                ..
+            }) => ConfigCompletion::Dictionaries {
+                quoted: value.matches('"').count() % 2 == 1,
+            },
+            _ => ConfigCompletion::None,
+        };
+    }
+
+    let mut path = table;
+    let key = prefix.trim();
+    if let Some((parents, _)) = key.rsplit_once('.') {
+        path.extend(parents.split('.').map(str::trim));
+    }
+    settings_in(&path).map_or(ConfigCompletion::None, ConfigCompletion::Keys)
+}
+
+/// Find the setting whose key is at a zero-based line and character
+pub fn setting_at(content: &str, line: u32, character: u32) -> Option<&'static SettingInfo> {
+    let lines: Vec<&str> = content.lines().collect();
+    let line = line as usize;
+    let current = *lines.get(line)?;
+    let trimmed = current.trim_start();
+    let (mut path, keys) = if trimmed.starts_with('[') {
+        (Vec::new(), current.trim_end().trim_end_matches(']'))
+    } else {
+        let (keys, _) = current.split_once('=')?;
+        (table_path(&lines[..line]), keys)
+    };
+    // Take the dotted parts of the key up to the one at the position
+    let mut start = 0;
+    for part in keys.split('.') {
+        let end = start + part.chars().count();
+        path.push(part.trim().trim_start_matches('['));
+        if (start..=end).contains(&(character as usize)) {
+            return find_setting(&path);
+        }
+        start = end + 1;
+    }
+    None
+}
+
+/// Key path of the last table header in `lines`
+fn table_path<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    lines
+        .iter()
+        .rev()
+        .map(|line| line.trim())
+        .find(|line| line.starts_with('['))
+        .map(|header| {
+            header
+                .trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or_default()
+                .split('.')
+                .map(str::trim)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Generate a JSON Schema of config files, for editors with TOML schema
+/// support. `dictionary_ids` are offered for dictionary settings.
+pub fn config_schema(dictionary_ids: &[String]) -> Value {
+    let mut schema = settings_schema();
+    resolve_extensions(&mut schema, dictionary_ids);
+    schema["title"] = json!("Codebook configuration");
+    schema
+}
+
+/// Replace the markers of `ConfigSettings` settings with what editors understand
+fn resolve_extensions(schema: &mut Value, dictionary_ids: &[String]) {
+    match schema {
+        Value::Object(object) => {
+            object.remove(GLOBAL_ONLY_EXTENSION);
+            // Not an enum, so dictionaries added later aren't rejected
+            if object.remove(DICTIONARIES_EXTENSION).is_some()
+                && let Some(items) = object.get_mut("items")
+            {
+                items["examples"] = json!(dictionary_ids);
+            }
+            for value in object.values_mut() {
+                resolve_extensions(value, dictionary_ids);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                resolve_extensions(value, dictionary_ids);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{DownloadSettings, Mirror, Override, PathDictionaries};
+
+    /// Every key `ConfigSettings` can hold must be described
+    #[test]
+    fn test_settings_cover_config() {
+        let settings = ConfigSettings {
+            dictionaries: vec!["en_us".to_string()],
+            words: vec!["a".to_string()],
+            flag_words: vec!["a".to_string()],
+            case_sensitive_words: vec!["A".to_string()],
+            ignore_paths: vec!["a".to_string()],
+            ignore_patterns: vec!["a".to_string()],
+            use_global: false,
+            max_suggestions: Some(1),
+            path_dictionaries: vec![PathDictionaries {
+                paths: vec!["a".to_string()],
+                dictionaries: vec!["a".to_string()],
+            }],
+            detect_language: Some(true),
+            download: DownloadSettings {
+                offline: Some(true),
+                proxy: Some("a".to_string()),
+                connect_timeout: Some(1),
+                read_timeout: Some(1),
+                refresh_days: Some(1),
+                mirrors: vec![Mirror {
+                    from: "a".to_string(),
+                    to: "a".to_string(),
+                }],
+            },
+            cache_dir: Some("a".to_string()),
+            extends: vec!["a".to_string()],
+            word_lists: vec!["a".to_string()],
+            overrides: vec![Override {
+                paths: vec!["a".to_string()],
+                languages: vec!["a".to_string()],
+                dictionaries: vec!["a".to_string()],
+                remove_dictionaries: vec!["a".to_string()],
+                words: vec!["a".to_string()],
+                remove_words: vec!["a".to_string()],
+                flag_words: vec!["a".to_string()],
+                remove_flag_words: vec!["a".to_string()],
+                ignore_patterns: vec!["a".to_string()],
+                remove_ignore_patterns: vec!["a".to_string()],
+            }],
+        };
+        let value = toml::Value::try_from(settings).unwrap();
+
+        fn check(table: &toml::Table, path: &mut Vec<String>) {
+            for (key, value) in table {
+                path.push(key.clone());
+                let keys: Vec<&str> = path.iter().map(String::as_str).collect();
+                let setting = find_setting(&keys);
+                assert!(
+                    setting.is_some_and(|s| !s.description.is_empty()),
+                    "{keys:?} isn't described"
+                );
+                match value {
+                    toml::Value::Table(table) => check(table, path),
+                    toml::Value::Array(values) => {
+                        for table in values.iter().filter_map(toml::Value::as_table) {
+                            check(table, path);
+                        }
+                    }
+                    _ => {}
+                }
+                path.pop();
+            }
+        }
+        check(value.as_table().unwrap(), &mut Vec::new());
+    }
+
+    #[test]
+    fn test_find_setting() {
+        assert!(find_setting(&["download", "mirrors", "from"]).is_some());
+        assert!(find_setting(&["overrides", "remove_words"]).is_some());
+        assert!(find_setting(&["words", "paths"]).is_none());
+        assert!(find_setting(&["unknown"]).is_none());
+        assert!(find_setting(&["cache_dir"]).unwrap().global_only);
+        assert!(find_setting(&["download", "mirrors"]).unwrap().global_only);
+        assert!(!find_setting(&["download", "offline"]).unwrap().global_only);
+        let kind = |path: &[&str]| &find_setting(path).unwrap().kind;
+        assert_eq!(kind(&["max_suggestions"]), &SettingKind::Integer);
+        assert_eq!(kind(&["cache_dir"]), &SettingKind::String);
+        assert_eq!(kind(&["download", "offline"]), &SettingKind::Bool);
+        assert_eq!(kind(&["word_lists"]), &SettingKind::Strings);
+        assert_eq!(
+            kind(&["overrides", "remove_dictionaries"]),
+            &SettingKind::Dictionaries
+        );
+        assert_eq!(settings_in(&[]).unwrap().len(), SETTINGS.len());
+    }
+
+    #[test]
+    fn test_completion_at() {
+        let content = "dic\ndictionaries = [\"en\"]\ndownload.pro\n\n[[overrides]]\nremove_dictionaries = [\n  \"en_us\",\n  \n]\nwords = [\"a\"]\n";
+        assert_eq!(
+            completion_at(content, 0, 3),
+            ConfigCompletion::Keys(&SETTINGS)
+        );
+        assert_eq!(
+            completion_at(content, 1, 19),
+            ConfigCompletion::Dictionaries { quoted: true }
+        );
+        assert_eq!(
+            completion_at(content, 2, 12),
+            ConfigCompletion::Keys(settings_in(&["download"]).unwrap())
+        );
+        assert_eq!(completion_at(content, 4, 2), ConfigCompletion::None);
+        // Inside an array spanning lines
+        assert_eq!(
+            completion_at(content, 7, 2),
+            ConfigCompletion::Dictionaries { quoted: false }
+        );
+        assert_eq!(
+            completion_at(content, 9, 0),
+            ConfigCompletion::Keys(settings_in(&["overrides"]).unwrap())
+        );
+        assert_eq!(completion_at(content, 9, 9), ConfigCompletion::None);
+    }
+
+    #[test]
+    fn test_setting_at() {
+        let content =
+            "use_global = true\ndownload.offline = true\n[[download.mirrors]]\nfrom = \"a\"\n";
+        assert_eq!(setting_at(content, 0, 2).unwrap().key, "use_global");
+        assert_eq!(setting_at(content, 0, 15), None);
+        assert_eq!(setting_at(content, 1, 3).unwrap().key, "download");
+        assert_eq!(setting_at(content, 1, 12).unwrap().key, "offline");
+        assert_eq!(setting_at(content, 2, 14).unwrap().key, "mirrors");
+        assert_eq!(setting_at(content, 3, 1).unwrap().key, "from");
+    }
+
+    #[test]
+    fn test_config_schema() {
+        let schema = config_schema(&["en_us".to_string()]);
+        assert_eq!(
+            schema["properties"]["dictionaries"]["items"]["examples"],
+            json!(["en_us"])
+        );
+        assert_eq!(
+            schema["properties"]["download"]["properties"]["mirrors"]["items"]["properties"]["to"]
+                ["type"],
+            "string"
+        );
+        assert_eq!(schema["additionalProperties"], false);
+    }
+}
@@ -1,32 +1,214 @@
+use crate::words::is_regex_entry;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-#[derive(Debug, Serialize, Clone, PartialEq)]
+
+// Doc comments of the settings are shown to users, in the JSON Schema and on hover
+#[derive(Debug, Serialize, Clone, PartialEq, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct ConfigSettings {
-    /// List of dictionaries to use for spell checking
+    /// Dictionaries to use for spell checking. Default: ["en_us"]
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(extend("x-dictionaries" = true))]
     pub dictionaries: Vec<String>,
 
-    /// Custom allowlist of words
+    /// Words to accept, case-insensitive. Entries can be globs (`*`, `?`, `[...]`),
+    /// or regexes starting with `^` matched against the word as written.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub words: Vec<String>,
 
-    /// Words that should always be flagged
+    /// Words to always flag as incorrect, with the same globs and regexes as `words`.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub flag_words: Vec<String>,
 
-    /// Glob patterns for paths to ignore
+    /// Words only correct exactly as written, like brand names. Other mixed-case
+    /// casings are flagged, all-lowercase and all-caps words are not.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub case_sensitive_words: Vec<String>,
+
+    /// Glob patterns of paths not to check, relative to the config file.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub ignore_paths: Vec<String>,
 
-    /// Regex patterns for text to ignore
+    /// Regex patterns of text not to check, matched against each line. Backslashes
+    /// must be escaped in TOML.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub ignore_patterns: Vec<String>,
 
-    /// Whether to use global configuration
+    /// Whether to merge the global config under this one. Default: true
     #[serde(
         default = "default_use_global",
         skip_serializing_if = "is_default_use_global"
     )]
     pub use_global: bool,
+
+    /// Maximum number of suggestions offered for a misspelled word. Default: 5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_suggestions: Option<usize>,
+
+    /// Dictionaries used instead of `dictionaries` for files matching glob patterns.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path_dictionaries: Vec<PathDictionaries>,
+
+    /// Check each comment, string or paragraph only against the configured
+    /// dictionary that fits it best. Default: false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detect_language: Option<bool>,
+
+    /// How dictionaries are downloaded.
+    #[serde(default, skip_serializing_if = "DownloadSettings::is_empty")]
+    pub download: DownloadSettings,
+
+    /// Directory in which the `codebook` cache directory of downloaded and compiled
+    /// dictionaries is kept, takes effect on restart. Only read from the global
+    /// config. The CODEBOOK_CACHE_DIR environment variable takes precedence, the
+    /// `codebook` directory is kept in it as well. Default: ~/.cache
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("x-global-only" = true))]
+    pub cache_dir: Option<String>,
+
+    /// Config files, as paths relative to this file or URLs, whose settings this
+    /// file builds on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extends: Vec<String>,
+
+    /// Files of words to accept, one per line, relative to this file. Lines
+    /// starting with `#` are comments.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub word_lists: Vec<String>,
+
+    /// Changes to the settings of files matching `paths` and `languages`, applied
+    /// in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overrides: Vec<Override>,
+}
+
+/// Settings for downloading dictionaries
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct DownloadSettings {
+    /// Never download, only use cached or bundled dictionaries. Default: false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offline: Option<bool>,
+
+    /// Proxy URL for all downloads. Only read from the global config. Default: the
+    /// HTTP_PROXY/HTTPS_PROXY environment variables
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(extend("x-global-only" = true))]
+    pub proxy: Option<String>,
+
+    /// Seconds to wait for a connection. Default: 10
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+
+    /// Seconds to wait for a whole download. Default: 60
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_timeout: Option<u64>,
+
+    /// Days a downloaded dictionary is used before checking for a newer version.
+    /// Default: 14
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_days: Option<u64>,
+
+    /// Download URLs starting with `from` are fetched from `to` instead. Only read
+    /// from the global config.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(extend("x-global-only" = true))]
+    pub mirrors: Vec<Mirror>,
+}
+
+/// Download URLs starting with `from` are fetched from `to` instead
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Mirror {
+    /// URL prefix to replace.
+    pub from: String,
+    /// URL prefix to download from instead.
+    pub to: String,
+}
+
+impl DownloadSettings {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merge another download settings into this one, set values from `other` take precedence
+    fn merge(&mut self, other: DownloadSettings) {
+        if other.offline.is_some() {
+            self.offline = other.offline;
+        }
+        if other.proxy.is_some() {
+            self.proxy = other.proxy;
+        }
+        if other.connect_timeout.is_some() {
+            self.connect_timeout = other.connect_timeout;
+        }
+        if other.read_timeout.is_some() {
+            self.read_timeout = other.read_timeout;
+        }
+        if other.refresh_days.is_some() {
+            self.refresh_days = other.refresh_days;
+        }
+        // Mirrors from `other` are tried first
+        let mut mirrors = other.mirrors;
+        for mirror in self.mirrors.drain(..) {
+            if !mirrors.contains(&mirror) {
+                mirrors.push(mirror);
+            }
+        }
+        self.mirrors = mirrors;
+    }
+}
+
+/// Dictionaries used for files matching any of the glob patterns in `paths`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct PathDictionaries {
+    /// Glob patterns of the files to use the dictionaries for.
+    pub paths: Vec<String>,
+    /// Dictionaries to use for the matching files.
+    #[schemars(extend("x-dictionaries" = true))]
+    pub dictionaries: Vec<String>,
+}
+
+/// Words, dictionaries and patterns added to or removed from the settings of
+/// files matching any of the glob patterns in `paths` and any of the language
+/// IDs in `languages`. An empty selector matches every file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Override {
+    /// Glob patterns of the files to change settings for. Default: all files
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    /// Language IDs of the files to change settings for, like "python". Default:
+    /// all languages
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+    /// Dictionaries to add.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(extend("x-dictionaries" = true))]
+    pub dictionaries: Vec<String>,
+    /// Dictionaries to remove.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(extend("x-dictionaries" = true))]
+    pub remove_dictionaries: Vec<String>,
+    /// Words to accept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<String>,
+    /// Words to no longer accept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove_words: Vec<String>,
+    /// Words to flag.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flag_words: Vec<String>,
+    /// Words to no longer flag.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove_flag_words: Vec<String>,
+    /// Regex patterns of text not to check.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_patterns: Vec<String>,
+    /// Ignore patterns to remove.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove_ignore_patterns: Vec<String>,
 }
 
 fn default_use_global() -> bool {
@@ -43,9 +225,18 @@ impl Default for ConfigSettings {
             dictionaries: vec![],
             words: Vec::new(),
             flag_words: Vec::new(),
+            case_sensitive_words: Vec::new(),
             ignore_paths: Vec::new(),
             ignore_patterns: Vec::new(),
             use_global: true,
+            max_suggestions: None,
+            path_dictionaries: Vec::new(),
+            detect_language: None,
+            download: DownloadSettings::default(),
+            cache_dir: None,
+            extends: Vec::new(),
+            word_lists: Vec::new(),
+            overrides: Vec::new(),
         }
     }
 }
@@ -58,6 +249,17 @@ impl<'de> Deserialize<'de> for ConfigSettings {
         fn to_lowercase_vec(v: Vec<String>) -> Vec<String> {
             v.into_iter().map(|s| s.to_ascii_lowercase()).collect()
         }
+        fn to_lowercase_words(v: Vec<String>) -> Vec<String> {
+            v.into_iter()
+                .map(|s| {
+                    if is_regex_entry(&s) {
+                        s
+                    } else {
+                        s.to_ascii_lowercase()
+                    }
+                })
+                .collect()
+        }
         #[derive(Deserialize)]
         struct Helper {
             #[serde(default)]
@@ -67,21 +269,68 @@ impl<'de> Deserialize<'de> for ConfigSettings {
             #[serde(default)]
             flag_words: Vec<String>,
             #[serde(default)]
+            case_sensitive_words: Vec<String>,
+            #[serde(default)]
             ignore_paths: Vec<String>,
             #[serde(default)]
             ignore_patterns: Vec<String>,
             #[serde(default = "default_use_global")]
             use_global: bool,
+            #[serde(default)]
+            max_suggestions: Option<usize>,
+            #[serde(default)]
+            path_dictionaries: Vec<PathDictionaries>,
+            #[serde(default)]
+            detect_language: Option<bool>,
+            #[serde(default)]
+            download: DownloadSettings,
+            #[serde(default)]
+            cache_dir: Option<String>,
+            #[serde(default)]
+            extends: Vec<String>,
+            #[serde(default)]
+            word_lists: Vec<String>,
+            #[serde(default)]
+            overrides: Vec<Override>,
         }
 
         let helper = Helper::deserialize(deserializer)?;
         Ok(ConfigSettings {
             dictionaries: to_lowercase_vec(helper.dictionaries),
-            words: to_lowercase_vec(helper.words),
-            flag_words: to_lowercase_vec(helper.flag_words),
+            words: to_lowercase_words(helper.words),
+            flag_words: to_lowercase_words(helper.flag_words),
+            case_sensitive_words: helper.case_sensitive_words,
             ignore_paths: helper.ignore_paths,
             ignore_patterns: helper.ignore_patterns,
             use_global: helper.use_global,
+            max_suggestions: helper.max_suggestions,
+            path_dictionaries: helper
+                .path_dictionaries
+                .into_iter()
+                .map(|p| PathDictionaries {
+                    paths: p.paths,
+                    dictionaries: to_lowercase_vec(p.dictionaries),
+                })
+                .collect(),
+            detect_language: helper.detect_language,
+            download: helper.download,
+            cache_dir: helper.cache_dir,
+            extends: helper.extends,
+            word_lists: helper.word_lists,
+            overrides: helper
+                .overrides
+                .into_iter()
+                .map(|o| Override {
+                    languages: to_lowercase_vec(o.languages),
+                    dictionaries: to_lowercase_vec(o.dictionaries),
+                    remove_dictionaries: to_lowercase_vec(o.remove_dictionaries),
+                    words: to_lowercase_words(o.words),
+                    remove_words: to_lowercase_words(o.remove_words),
+                    flag_words: to_lowercase_words(o.flag_words),
+                    remove_flag_words: to_lowercase_words(o.remove_flag_words),
+                    ..o
+                })
+                .collect(),
         })
     }
 }
@@ -93,11 +342,35 @@ impl ConfigSettings {
         self.dictionaries.extend(other.dictionaries);
         self.words.extend(other.words);
         self.flag_words.extend(other.flag_words);
+        self.case_sensitive_words.extend(other.case_sensitive_words);
         self.ignore_paths.extend(other.ignore_paths);
         self.ignore_patterns.extend(other.ignore_patterns);
+        for path_dictionaries in other.path_dictionaries {
+            if !self.path_dictionaries.contains(&path_dictionaries) {
+                self.path_dictionaries.push(path_dictionaries);
+            }
+        }
+        // Overrides apply in order, so those from the other config come last
+        for config_override in other.overrides {
+            if !self.overrides.contains(&config_override) {
+                self.overrides.push(config_override);
+            }
+        }
+
+        // The use_global, extends and word_lists settings from the other config
+        // are ignored during merging as these are per-config settings
 
-        // The use_global setting from the other config is ignored during merging
-        // as this is a per-config setting
+        // Scalar settings from the other config take precedence when set
+        if other.max_suggestions.is_some() {
+            self.max_suggestions = other.max_suggestions;
+        }
+        if other.detect_language.is_some() {
+            self.detect_language = other.detect_language;
+        }
+        self.download.merge(other.download);
+        if other.cache_dir.is_some() {
+            self.cache_dir = other.cache_dir;
+        }
 
         // Sort and deduplicate each collection
         self.sort_and_dedup();
@@ -109,6 +382,7 @@ impl ConfigSettings {
         sort_and_dedup(&mut self.dictionaries);
         sort_and_dedup(&mut self.words);
         sort_and_dedup(&mut self.flag_words);
+        sort_and_dedup(&mut self.case_sensitive_words);
         sort_and_dedup(&mut self.ignore_paths);
         sort_and_dedup(&mut self.ignore_patterns);
     }
@@ -139,18 +413,48 @@ mod tests {
     fn test_deserialization() {
         let toml_str = r#"
         dictionaries = ["EN_US", "en_GB"]
-        words = ["CodeBook", "Rust"]
+        words = ["CodeBook", "Rust", "K8s*", "^x[A-Z]{2}\\S+$"]
         flag_words = ["TODO", "FIXME"]
+        case_sensitive_words = ["GitHub", "iOS"]
         ignore_paths = ["**/*.md", "target/"]
         ignore_patterns = ["^```.*$", "^//.*$"]
         use_global = false
+        max_suggestions = 8
+        detect_language = true
+        cache_dir = "~/.cache/codebook"
+        extends = ["../shared/codebook.toml"]
+        word_lists = ["project-words.txt"]
+
+        [[path_dictionaries]]
+        paths = ["docs/de/**"]
+        dictionaries = ["DE"]
+
+        [[overrides]]
+        paths = ["tests/**"]
+        languages = ["Python"]
+        words = ["FooBar"]
+        remove_dictionaries = ["EN_US"]
+
+        [download]
+        offline = true
+        connect_timeout = 5
+
+        [[download.mirrors]]
+        from = "https://raw.githubusercontent.com/"
+        to = "https://mirror.example.com/"
         "#;
 
         let config: ConfigSettings = toml::from_str(toml_str).unwrap();
 
         assert_eq!(config.dictionaries, vec!["en_us", "en_gb"]);
-        assert_eq!(config.words, vec!["codebook", "rust"]);
+        // Regexes keep their casing
+        assert_eq!(
+            config.words,
+            vec!["codebook", "rust", "k8s*", r"^x[A-Z]{2}\S+$"]
+        );
         assert_eq!(config.flag_words, vec!["todo", "fixme"]);
+        // Case-sensitive words are kept as written
+        assert_eq!(config.case_sensitive_words, vec!["GitHub", "iOS"]);
         assert_eq!(config.ignore_paths, vec!["**/*.md", "target/"]);
 
         // Don't test the exact order, just check that both elements are present
@@ -159,6 +463,38 @@ mod tests {
         assert!(config.ignore_patterns.contains(&"^//.*$".to_string()));
 
         assert!(!config.use_global);
+        assert_eq!(config.max_suggestions, Some(8));
+        assert_eq!(config.detect_language, Some(true));
+        assert_eq!(config.cache_dir.as_deref(), Some("~/.cache/codebook"));
+        assert_eq!(config.extends, vec!["../shared/codebook.toml"]);
+        assert_eq!(config.word_lists, vec!["project-words.txt"]);
+        assert_eq!(
+            config.path_dictionaries,
+            vec![PathDictionaries {
+                paths: vec!["docs/de/**".to_string()],
+                dictionaries: vec!["de".to_string()],
+            }]
+        );
+        assert_eq!(
+            config.overrides,
+            vec![Override {
+                paths: vec!["tests/**".to_string()],
+                languages: vec!["python".to_string()],
+                words: vec!["foobar".to_string()],
+                remove_dictionaries: vec!["en_us".to_string()],
+                ..Default::default()
+            }]
+        );
+        assert_eq!(config.download.offline, Some(true));
+        assert_eq!(config.download.connect_timeout, Some(5));
+        assert_eq!(config.download.read_timeout, None);
+        assert_eq!(
+            config.download.mirrors,
+            vec![Mirror {
+                from: "https://raw.githubusercontent.com/".to_string(),
+                to: "https://mirror.example.com/".to_string(),
+            }]
+        );
     }
 
     #[test]
@@ -174,6 +510,8 @@ mod tests {
         assert!(serialized.contains("words = [\"rust\"]"));
         // Defaults should not be there
         assert!(!serialized.contains("use_global = true"));
+        assert!(!serialized.contains("max_suggestions"));
+        assert!(!serialized.contains("download"));
     }
 
     #[test]
@@ -182,18 +520,71 @@ mod tests {
             dictionaries: vec!["en_us".to_string()],
             words: vec!["codebook".to_string()],
             flag_words: vec!["todo".to_string()],
+            case_sensitive_words: vec!["GitHub".to_string()],
             ignore_paths: vec!["**/*.md".to_string()],
             ignore_patterns: vec!["^```.*$".to_string()],
             use_global: true,
+            max_suggestions: Some(5),
+            path_dictionaries: vec![PathDictionaries {
+                paths: vec!["docs/de/**".to_string()],
+                dictionaries: vec!["de".to_string()],
+            }],
+            detect_language: Some(true),
+            download: DownloadSettings {
+                offline: Some(false),
+                read_timeout: Some(30),
+                mirrors: vec![Mirror {
+                    from: "https://a/".to_string(),
+                    to: "https://b/".to_string(),
+                }],
+                ..Default::default()
+            },
+            cache_dir: Some("/tmp/base".to_string()),
+            extends: vec!["base.toml".to_string()],
+            word_lists: vec!["base.txt".to_string()],
+            overrides: vec![Override {
+                paths: vec!["tests/**".to_string()],
+                words: vec!["foobar".to_string()],
+                ..Default::default()
+            }],
         };
 
         let other = ConfigSettings {
             dictionaries: vec!["en_gb".to_string(), "en_us".to_string()],
             words: vec!["rust".to_string()],
             flag_words: vec!["fixme".to_string()],
+            case_sensitive_words: vec!["iOS".to_string(), "GitHub".to_string()],
             ignore_paths: vec!["target/".to_string()],
             ignore_patterns: vec!["^//.*$".to_string()],
             use_global: false,
+            max_suggestions: Some(8),
+            path_dictionaries: vec![
+                PathDictionaries {
+                    paths: vec!["docs/de/**".to_string()],
+                    dictionaries: vec!["de".to_string()],
+                },
+                PathDictionaries {
+                    paths: vec!["docs/es/**".to_string()],
+                    dictionaries: vec!["es".to_string()],
+                },
+            ],
+            detect_language: None,
+            download: DownloadSettings {
+                offline: Some(true),
+                mirrors: vec![Mirror {
+                    from: "https://c/".to_string(),
+                    to: "https://d/".to_string(),
+                }],
+                ..Default::default()
+            },
+            cache_dir: Some("/tmp/other".to_string()),
+            extends: vec!["other.toml".to_string()],
+            word_lists: vec!["other.txt".to_string()],
+            overrides: vec![Override {
+                languages: vec!["python".to_string()],
+                remove_words: vec!["foobar".to_string()],
+                ..Default::default()
+            }],
         };
 
         base.merge(other);
@@ -202,6 +593,7 @@ mod tests {
         assert_eq!(base.dictionaries, vec!["en_gb", "en_us"]);
         assert_eq!(base.words, vec!["codebook", "rust"]);
         assert_eq!(base.flag_words, vec!["fixme", "todo"]);
+        assert_eq!(base.case_sensitive_words, vec!["GitHub", "iOS"]);
         assert_eq!(base.ignore_paths, vec!["**/*.md", "target/"]);
 
         // Don't test the exact order, just check that both elements are present
@@ -211,6 +603,27 @@ mod tests {
 
         // use_global from the base should be preserved
         assert!(base.use_global);
+
+        // max_suggestions from the other config takes precedence
+        assert_eq!(base.max_suggestions, Some(8));
+
+        // Path dictionaries are combined without duplicates
+        assert_eq!(base.path_dictionaries.len(), 2);
+        assert_eq!(base.path_dictionaries[1].dictionaries, vec!["es"]);
+
+        // Unset scalar settings don't override
+        assert_eq!(base.detect_language, Some(true));
+        assert_eq!(base.download.read_timeout, Some(30));
+        assert_eq!(base.download.offline, Some(true));
+        assert_eq!(base.download.mirrors.len(), 2);
+        assert_eq!(base.download.mirrors[0].from, "https://c/");
+        assert_eq!(base.cache_dir.as_deref(), Some("/tmp/other"));
+        assert_eq!(base.extends, vec!["base.toml"]);
+        assert_eq!(base.word_lists, vec!["base.txt"]);
+
+        // Overrides keep their order
+        assert_eq!(base.overrides.len(), 2);
+        assert_eq!(base.overrides[1].languages, vec!["python"]);
     }
 
     #[test]
@@ -227,6 +640,7 @@ mod tests {
                 "rust".to_string(),
             ],
             flag_words: vec!["fixme".to_string(), "todo".to_string(), "fixme".to_string()],
+            case_sensitive_words: vec!["iOS".to_string(), "IOS".to_string(), "iOS".to_string()],
             ignore_paths: vec![
                 "target/".to_string(),
                 "**/*.md".to_string(),
@@ -238,6 +652,14 @@ mod tests {
                 "^//.*$".to_string(),
             ],
             use_global: true,
+            max_suggestions: None,
+            path_dictionaries: Vec::new(),
+            detect_language: None,
+            download: DownloadSettings::default(),
+            cache_dir: None,
+            extends: Vec::new(),
+            word_lists: Vec::new(),
+            overrides: Vec::new(),
         };
 
         config.sort_and_dedup();
@@ -245,6 +667,7 @@ mod tests {
         assert_eq!(config.dictionaries, vec!["en_gb", "en_us"]);
         assert_eq!(config.words, vec!["codebook", "rust"]);
         assert_eq!(config.flag_words, vec!["fixme", "todo"]);
+        assert_eq!(config.case_sensitive_words, vec!["IOS", "iOS"]);
         assert_eq!(config.ignore_paths, vec!["**/*.md", "target/"]);
 
         // Don't test the exact order, just check that both elements are present and duplicates removed
@@ -0,0 +1,255 @@
+use crate::schema::{SETTINGS, SettingInfo, SettingKind};
+use crate::settings::ConfigSettings;
+use crate::words::is_regex_entry;
+use glob::Pattern;
+use regex::Regex;
+use std::ops::Range;
+use toml_edit::{ImDocument, Item, TableLike, Value};
+
+/// A problem found in a config file. Lines and characters are zero-based.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub message: String,
+    pub start_line: u32,
+    pub start_char: u32,
+    pub end_line: u32,
+    pub end_char: u32,
+}
+
+/// Check the content of a config file for syntax errors, values of the wrong
+/// type, unknown keys, invalid regexes and globs, and dictionaries for which
+/// `is_known_dictionary` returns false. Settings only read from the global
+/// config are reported in other config files, where they're ignored.
+pub fn validate_config(
+    content: &str,
+    is_global: bool,
+    is_known_dictionary: impl Fn(&str) -> bool,
+) -> Vec<ConfigIssue> {
+    let mut validator = Validator {
+        content,
+        is_global,
+        is_known_dictionary: &is_known_dictionary,
+        issues: Vec::new(),
+    };
+    let document = match ImDocument::parse(content) {
+        Ok(document) => document,
+        Err(e) => {
+            validator.issue(e.span(), e.message().to_string());
+            return validator.issues;
+        }
+    };
+    if let Err(e) = toml::from_str::<ConfigSettings>(content) {
+        validator.issue(e.span(), e.message().to_string());
+    }
+    validator.check_table(document.as_table(), &SETTINGS);
+    validator.issues
+}
+
+struct Validator<'a> {
+    content: &'a str,
+    is_global: bool,
+    is_known_dictionary: &'a dyn Fn(&str) -> bool,
+    issues: Vec<ConfigIssue>,
+}
+
+impl Validator<'_> {
+    fn check_table(&mut self, table: &dyn TableLike, settings: &[SettingInfo]) {
+        for (key, item) in table.iter() {
+            let Some(setting) = settings.iter().find(|s| s.key == key) else {
+                let span = table
+                    .key(key)
+                    .and_then(|k| k.span())
+                    .or_else(|| item.span());
+                self.issue(span, format!("Unknown key '{}'", key));
+                continue;
+            };
+            if setting.global_only && !self.is_global {
+                let span = table
+                    .key(key)
+                    .and_then(|k| k.span())
+                    .or_else(|| item.span());
+                self.issue(
+                    span,
+                    format!("'{}' is only read from the global config", key),
+                );
+            }
+            match (&setting.kind, key) {
+                (SettingKind::Dictionaries, _) => self.check_strings(item, |id| {
+                    (!(self.is_known_dictionary)(&id.to_ascii_lowercase()))
+                        .then(|| format!("Unknown dictionary '{}'", id))
+                }),
+                (SettingKind::Table(settings), _) => {
+                    if let Some(table) = item.as_table_like() {
+                        self.check_table(table, settings);
+                    }
+                }
+                (SettingKind::Tables(settings), _) => self.check_tables(item, settings),
+                (_, "ignore_patterns" | "remove_ignore_patterns") => self
+                    .check_strings(item, |pattern| {
+                        Regex::new(pattern).err().map(|e| e.to_string())
+                    }),
+                (_, "words" | "flag_words" | "remove_words" | "remove_flag_words") => {
+                    self.check_strings(item, check_word_entry)
+                }
+                (_, "ignore_paths" | "paths") => self.check_strings(item, |pattern| {
+                    Pattern::new(pattern)
+                        .err()
+                        .map(|e| format!("Invalid glob pattern: {}", e))
+                }),
+                _ => {}
+            }
+        }
+    }
+
+    /// Check each table of an array of tables, in either syntax
+    fn check_tables(&mut self, item: &Item, settings: &[SettingInfo]) {
+        match item {
+            Item::ArrayOfTables(tables) => {
+                for table in tables.iter() {
+                    self.check_table(table, settings);
+                }
+            }
+            Item::Value(Value::Array(values)) => {
+                for table in values.iter().filter_map(Value::as_inline_table) {
+                    self.check_table(table, settings);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Check each string of an array, `check` returns the problem if any
+    fn check_strings(&mut self, item: &Item, check: impl Fn(&str) -> Option<String>) {
+        let Some(values) = item.as_array() else {
+            return;
+        };
+        let issues: Vec<_> = values
+            .iter()
+            .filter_map(|value| Some((value.span(), check(value.as_str()?)?)))
+            .collect();
+        for (span, message) in issues {
+            self.issue(span, message);
+        }
+    }
+
+    fn issue(&mut self, span: Option<Range<usize>>, message: String) {
+        let span = span.unwrap_or(0..0);
+        let (start_line, start_char) = position(self.content, span.start);
+        let (end_line, end_char) = position(self.content, span.end);
+        self.issues.push(ConfigIssue {
+            message,
+            start_line,
+            start_char,
+            end_line,
+            end_char,
+        });
+    }
+}
+
+fn check_word_entry(entry: &str) -> Option<String> {
+    if is_regex_entry(entry) {
+        Regex::new(entry).err().map(|e| e.to_string())
+    } else if entry.contains(['*', '?', '[']) {
+        Pattern::new(entry)
+            .err()
+            .map(|e| format!("Invalid glob pattern: {}", e))
+    } else {
+        None
+    }
+}
+
+/// Line and character of a byte offset
+fn position(content: &str, offset: usize) -> (u32, u32) {
+    let before = &content[..offset.min(content.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() as u32,
+        before[line_start..].chars().count() as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(content: &str) -> Vec<String> {
+        validate_config(content, false, |id| id == "en_us")
+            .into_iter()
+            .map(|issue| issue.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let settings = ConfigSettings {
+            dictionaries: vec!["en_us".to_string()],
+            words: vec!["k8s*".to_string(), r"^x\d+$".to_string()],
+            overrides: vec![Default::default()],
+            ..Default::default()
+        };
+        let content = toml::to_string_pretty(&settings).unwrap();
+        assert_eq!(messages(&content), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_issue_locations() {
+        let content = "dictionaries = [\"en_us\", \"xx\"]\nignore_patterns = [\"(\"]\n";
+        let issues = validate_config(content, false, |id| id == "en_us");
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].message, "Unknown dictionary 'xx'");
+        assert_eq!(
+            (
+                issues[0].start_line,
+                issues[0].start_char,
+                issues[0].end_char
+            ),
+            (0, 25, 29)
+        );
+        assert_eq!(issues[1].start_line, 1);
+        assert_eq!(issues[1].start_char, 19);
+    }
+
+    #[test]
+    fn test_global_only_settings() {
+        let content =
+            "cache_dir = \"~/cache\"\n[download]\nproxy = \"http://proxy\"\noffline = true\n";
+        assert_eq!(
+            messages(content),
+            vec![
+                "'cache_dir' is only read from the global config",
+                "'proxy' is only read from the global config",
+            ]
+        );
+        assert!(validate_config(content, true, |id| id == "en_us").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let content = r#"
+words = ["^(", "[a"]
+ignore_paths = ["[b"]
+unknown = 1
+
+[download]
+ofline = true
+
+[[overrides]]
+paths = ["tests/**"]
+langauges = ["python"]
+remove_dictionaries = ["de"]
+"#;
+        let found = messages(content);
+        assert_eq!(found.len(), 7, "{found:?}");
+        assert!(found.contains(&"Unknown key 'unknown'".to_string()));
+        assert!(found.contains(&"Unknown key 'ofline'".to_string()));
+        assert!(found.contains(&"Unknown key 'langauges'".to_string()));
+        assert!(found.contains(&"Unknown dictionary 'de'".to_string()));
+
+        // Syntax and type errors
+        assert_eq!(messages("words = [").len(), 1);
+        assert_eq!(
+            messages("max_suggestions = \"five\""),
+            vec!["invalid type: string \"five\", expected usize"]
+        );
+    }
+}
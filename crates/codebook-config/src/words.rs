@@ -0,0 +1,96 @@
+use glob::Pattern;
+use log::error;
+use regex::Regex;
+use std::collections::HashSet;
+
+/// Matches words against a word list such as `words` or `flag_words`. Entries
+/// are plain words, globs like `k8s*` or, when starting with `^`, regexes like
+/// `^x[A-Z]{2}\d+$`. Plain words and globs are lowercase and match any casing,
+/// regexes match the word as written.
+#[derive(Debug, Default)]
+pub(crate) struct WordMatcher {
+    words: HashSet<String>,
+    globs: Vec<Pattern>,
+    regexes: Vec<Regex>,
+}
+
+impl WordMatcher {
+    pub(crate) fn new(entries: &[String]) -> Self {
+        let mut matcher = Self::default();
+        for entry in entries {
+            if is_regex_entry(entry) {
+                match Regex::new(entry) {
+                    Ok(regex) => matcher.regexes.push(regex),
+                    Err(e) => error!("Invalid word pattern '{}': {}", entry, e),
+                }
+            } else if entry.contains(['*', '?', '[']) {
+                match Pattern::new(entry) {
+                    Ok(glob) => matcher.globs.push(glob),
+                    Err(e) => error!("Invalid word pattern '{}': {}", entry, e),
+                }
+            } else {
+                matcher.words.insert(entry.clone());
+            }
+        }
+        matcher
+    }
+
+    pub(crate) fn matches(&self, word: &str) -> bool {
+        let lowercase = word.to_ascii_lowercase();
+        self.words.contains(&lowercase)
+            || self.globs.iter().any(|glob| glob.matches(&lowercase))
+            || self.regexes.iter().any(|regex| regex.is_match(word))
+    }
+}
+
+/// Words of a word list file, one per line. Empty lines and lines starting
+/// with `#` are skipped, entries are lowercased like those of `words`.
+pub(crate) fn parse_word_list(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            if is_regex_entry(line) {
+                line.to_string()
+            } else {
+                line.to_ascii_lowercase()
+            }
+        })
+        .collect()
+}
+
+/// Regex entries keep their casing, `\S` and `\s` mean different things
+pub(crate) fn is_regex_entry(entry: &str) -> bool {
+    entry.starts_with('^')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_matcher() {
+        let entries = ["codebook", "k8s*", r"^x[A-Z]{2}\d+$", "^(", "[a"]
+            .map(String::from)
+            .to_vec();
+        let matcher = WordMatcher::new(&entries);
+        assert!(matcher.matches("CodeBook"));
+        assert!(matcher.matches("k8s"));
+        assert!(matcher.matches("K8sCluster"));
+        assert!(matcher.matches("xAB12"));
+        assert!(!matcher.matches("xab12"));
+        assert!(!matcher.matches("codebooks"));
+        // Invalid patterns are skipped
+        assert!(!matcher.matches("[a"));
+    }
+
+    #[test]
+    fn test_parse_word_list() {
+        let content = "# Team words\nKubernetes\n\n  k8s*  \n^x\\d+$\n";
+        assert_eq!(
+            parse_word_list(content),
+            vec!["kubernetes", "k8s*", r"^x\d+$"]
+        );
+    }
+}
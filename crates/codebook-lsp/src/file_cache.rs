@@ -3,6 +3,7 @@ use std::{
     sync::{Arc, RwLock},
 };
 
+use codebook::parser::WordLocation;
 use lru::LruCache;
 use tower_lsp::lsp_types::{TextDocumentItem, Url};
 
@@ -12,6 +13,8 @@ pub struct TextDocumentCacheItem {
     pub uri: Url,
     pub version: Option<i32>,
     pub language_id: Option<String>,
+    /// Results of the last spell check of `text`, if it was checked
+    pub spell_results: Option<Arc<Vec<WordLocation>>>,
 }
 
 impl TextDocumentCacheItem {
@@ -29,6 +32,7 @@ impl TextDocumentCacheItem {
                 Some(text) => text.to_string(),
                 None => String::new(),
             },
+            spell_results: None,
         }
     }
 }
@@ -85,6 +89,16 @@ impl TextDocumentCache {
         }
     }
 
+    /// Keep the results of spell checking `text`, unless the document changed since
+    pub fn set_spell_results(&self, uri: &Url, text: &str, results: Vec<WordLocation>) {
+        let mut cache = self.documents.write().unwrap();
+        if let Some(item) = cache.get_mut(uri.as_str())
+            && item.text == text
+        {
+            item.spell_results = Some(Arc::new(results));
+        }
+    }
+
     pub fn remove(&self, uri: &Url) {
         self.documents.write().unwrap().pop(uri.as_str());
     }
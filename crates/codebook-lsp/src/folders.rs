@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use codebook::Codebook;
+use codebook_config::CodebookConfig;
+use tower_lsp::lsp_types::Url;
+
+/// The config and spell checker of a workspace folder
+pub struct Folder {
+    pub root: PathBuf,
+    pub config: Arc<CodebookConfig>,
+    // Wrap every call to codebook in spawn_blocking, it's not async
+    pub codebook: Arc<Codebook>,
+}
+
+impl Folder {
+    /// Load the config of a folder. Its spell checker shares the dictionaries
+    /// of `shared`, so they are only loaded once for all folders.
+    pub fn load(
+        root: &Path,
+        shared: Option<&Codebook>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let config = Arc::new(CodebookConfig::load(Some(root))?);
+        let codebook = match shared {
+            Some(shared) => shared.with_config(Arc::clone(&config)),
+            None => Codebook::new(Arc::clone(&config))?,
+        };
+        let codebook = Arc::new(codebook);
+        Ok(Self {
+            // Paths of documents are absolute
+            root: std::path::absolute(root)?,
+            config,
+            codebook,
+        })
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+}
+
+/// The workspace folders opened by the client. Documents outside of all of
+/// them, or all documents if the client has no folders, belong to the folder
+/// the server was started in.
+pub struct WorkspaceFolders {
+    default: Arc<Folder>,
+    folders: RwLock<Vec<Arc<Folder>>>,
+}
+
+impl WorkspaceFolders {
+    pub fn new(default: Folder) -> Self {
+        Self {
+            default: Arc::new(default),
+            folders: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// The spell checker of the default folder, whose dictionaries all folders share
+    pub fn default_codebook(&self) -> Arc<Codebook> {
+        Arc::clone(&self.default.codebook)
+    }
+
+    /// The folder a file belongs to, the innermost one for nested folders
+    pub fn for_path(&self, path: &Path) -> Arc<Folder> {
+        self.folders
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|folder| folder.contains(path))
+            .max_by_key(|folder| folder.root.components().count())
+            .cloned()
+            .unwrap_or_else(|| Arc::clone(&self.default))
+    }
+
+    /// The folder a document belongs to, the default one if it isn't a file
+    pub fn for_uri(&self, uri: &Url) -> Arc<Folder> {
+        match uri.to_file_path() {
+            Ok(path) => self.for_path(&path),
+            Err(_) => Arc::clone(&self.default),
+        }
+    }
+
+    /// The workspace folders, or the default folder if there are none
+    pub fn all(&self) -> Vec<Arc<Folder>> {
+        let folders = self.folders.read().unwrap();
+        match folders.is_empty() {
+            true => vec![Arc::clone(&self.default)],
+            false => folders.clone(),
+        }
+    }
+
+    pub fn contains_root(&self, root: &Path) -> bool {
+        self.folders
+            .read()
+            .unwrap()
+            .iter()
+            .any(|folder| folder.root == root)
+    }
+
+    /// A folder at `root` sharing the config of the default folder, if the
+    /// server was started in it, so it isn't loaded twice
+    pub fn reuse_default(&self, root: &Path) -> Option<Folder> {
+        same_path(root, &self.default.root).then(|| Folder {
+            root: root.to_path_buf(),
+            config: Arc::clone(&self.default.config),
+            codebook: Arc::clone(&self.default.codebook),
+        })
+    }
+
+    pub fn add(&self, folder: Arc<Folder>) {
+        self.folders.write().unwrap().push(folder);
+    }
+
+    pub fn remove(&self, root: &Path) {
+        self.folders
+            .write()
+            .unwrap()
+            .retain(|folder| folder.root != root);
+    }
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
@@ -1,3 +1,5 @@
 pub mod file_cache;
+pub mod folders;
 pub mod lsp;
 pub mod lsp_logger;
+pub mod workspace;
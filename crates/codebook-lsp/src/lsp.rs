@@ -1,49 +1,91 @@
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr as _;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use codebook::casing::apply_suggestion;
+use codebook::dictionaries::repo::{get_dictionary_ids, get_repo};
+use codebook::directives::add_word_directive;
 use codebook::parser::TextRange;
 use codebook::parser::WordLocation;
-use codebook::parser::get_word_from_string;
+use codebook::parser::{get_identifier_at, get_word_at, get_word_from_string};
 use codebook::queries::LanguageType;
 
 use log::LevelFilter;
 use log::error;
-use serde_json::Value;
+use serde::Deserialize;
+use serde_json::{Value, json};
 use tokio::task;
 use tower_lsp::jsonrpc::Result as RpcResult;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
-use codebook::Codebook;
-use codebook_config::CodebookConfig;
+use codebook::WordExplanation;
+use codebook_config::schema::{ConfigCompletion, SettingInfo, completion_at, setting_at};
+use codebook_config::{ConfigIssue, validate_config};
 use log::{debug, info};
 
-use crate::file_cache::TextDocumentCache;
+use crate::file_cache::{TextDocumentCache, TextDocumentCacheItem};
+use crate::folders::{Folder, WorkspaceFolders};
 use crate::lsp_logger;
+use crate::workspace::{WorkspaceSummary, workspace_files};
 
 const SOURCE_NAME: &str = "Codebook";
+/// Source of problems in config files, which have no spelling code actions
+const CONFIG_SOURCE_NAME: &str = "Codebook config";
+const LOAD_PROGRESS_TOKEN: &str = "codebook/loadDictionaries";
+const CHECK_PROGRESS_TOKEN: &str = "codebook/checkWorkspace";
+/// Unknown words listed in the `codebook.checkWorkspace` summary
+const SUMMARY_WORD_LIMIT: usize = 50;
 
 pub struct Backend {
     pub client: Client,
-    // Wrap every call to codebook in spawn_blocking, it's not async
-    pub codebook: Arc<Codebook>,
-    pub config: Arc<CodebookConfig>,
+    /// Config and spell checker of each workspace folder
+    pub folders: WorkspaceFolders,
     pub document_cache: TextDocumentCache,
+    /// Set once dictionaries are preloaded, documents aren't checked before that
+    dictionaries_ready: AtomicBool,
+    /// Whether the client shows `$/progress` notifications
+    supports_progress: AtomicBool,
 }
 
 enum CodebookCommand {
     AddWord,
     AddWordGlobal,
+    RemoveWord,
+    IgnoreFile,
+    FlagWord,
+    AddWordToList,
+    CheckWorkspace,
+    AddUnknownWords,
     Unknown,
 }
 
+/// Options of the `codebook.addUnknownWords` command, which also accepts just
+/// a file URI
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct AddUnknownWordsOptions {
+    /// File to take the words from, the whole workspace if not set
+    uri: Option<Url>,
+    /// Only add words appearing at least this many times
+    min_count: usize,
+    /// Add the words to the global config instead of the project config
+    global: bool,
+}
+
 impl From<&str> for CodebookCommand {
     fn from(command: &str) -> Self {
         match command {
             "codebook.addWord" => CodebookCommand::AddWord,
             "codebook.addWordGlobal" => CodebookCommand::AddWordGlobal,
+            "codebook.removeWord" => CodebookCommand::RemoveWord,
+            "codebook.ignoreFile" => CodebookCommand::IgnoreFile,
+            "codebook.flagWord" => CodebookCommand::FlagWord,
+            "codebook.addWordToList" => CodebookCommand::AddWordToList,
+            "codebook.checkWorkspace" => CodebookCommand::CheckWorkspace,
+            "codebook.addUnknownWords" => CodebookCommand::AddUnknownWords,
             _ => CodebookCommand::Unknown,
         }
     }
@@ -54,6 +96,12 @@ impl From<CodebookCommand> for String {
         match command {
             CodebookCommand::AddWord => "codebook.addWord".to_string(),
             CodebookCommand::AddWordGlobal => "codebook.addWordGlobal".to_string(),
+            CodebookCommand::RemoveWord => "codebook.removeWord".to_string(),
+            CodebookCommand::IgnoreFile => "codebook.ignoreFile".to_string(),
+            CodebookCommand::FlagWord => "codebook.flagWord".to_string(),
+            CodebookCommand::AddWordToList => "codebook.addWordToList".to_string(),
+            CodebookCommand::CheckWorkspace => "codebook.checkWorkspace".to_string(),
+            CodebookCommand::AddUnknownWords => "codebook.addUnknownWords".to_string(),
             CodebookCommand::Unknown => "codebook.unknown".to_string(),
         }
     }
@@ -79,6 +127,26 @@ impl LanguageServer for Backend {
         lsp_logger::LspLogger::init(self.client.clone(), log_level)
             .expect("Failed to initialize LSP logger");
         info!("LSP logger initialized with log level: {}", log_level);
+        let supports_progress = params
+            .capabilities
+            .window
+            .as_ref()
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or(false);
+        self.supports_progress
+            .store(supports_progress, Ordering::Relaxed);
+        // Clients without workspace folders only send the root
+        let roots: Vec<PathBuf> = match (params.workspace_folders, params.root_uri) {
+            (Some(folders), _) => folders.iter().map(|folder| folder.uri.clone()).collect(),
+            (None, Some(root_uri)) => vec![root_uri],
+            (None, None) => Vec::new(),
+        }
+        .iter()
+        .filter_map(|uri| uri.to_file_path().ok())
+        .collect();
+        for root in roots {
+            self.add_folder(root).await;
+        }
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
@@ -88,6 +156,12 @@ impl LanguageServer for Backend {
                     commands: vec![
                         CodebookCommand::AddWord.into(),
                         CodebookCommand::AddWordGlobal.into(),
+                        CodebookCommand::RemoveWord.into(),
+                        CodebookCommand::IgnoreFile.into(),
+                        CodebookCommand::FlagWord.into(),
+                        CodebookCommand::AddWordToList.into(),
+                        CodebookCommand::CheckWorkspace.into(),
+                        CodebookCommand::AddUnknownWords.into(),
                     ],
                     work_done_progress_options: Default::default(),
                 }),
@@ -100,6 +174,19 @@ impl LanguageServer for Backend {
                         },
                     },
                 )),
+                // Only config files get completion and hover
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec!["\"".to_string(), "[".to_string()]),
+                    ..Default::default()
+                }),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
                 ..ServerCapabilities::default()
             },
             server_info: Some(ServerInfo {
@@ -111,22 +198,51 @@ impl LanguageServer for Backend {
 
     async fn initialized(&self, _: InitializedParams) {
         info!("Server ready!");
-        info!(
-            "Project config: {}",
-            self.config
-                .project_config_path
-                .clone()
-                .unwrap_or_default()
-                .display()
-        );
+        let folders = self.folders.all();
+        for folder in &folders {
+            info!(
+                "Project config of {}: {}",
+                folder.root.display(),
+                folder
+                    .config
+                    .project_config_path
+                    .clone()
+                    .unwrap_or_default()
+                    .display()
+            );
+        }
         info!(
             "Global config: {}",
-            self.config
+            folders[0]
+                .config
                 .global_config_path
                 .clone()
                 .unwrap_or_default()
                 .display()
         );
+        self.preload_dictionaries(&folders).await;
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        for removed in params.event.removed {
+            if let Ok(root) = removed.uri.to_file_path() {
+                info!("Removing workspace folder {}", root.display());
+                self.folders.remove(&root);
+            }
+        }
+        let mut added = Vec::new();
+        for folder in params.event.added {
+            if let Ok(root) = folder.uri.to_file_path() {
+                info!("Adding workspace folder {}", root.display());
+                added.extend(self.add_folder(root).await);
+            }
+        }
+        if added.is_empty() || !self.dictionaries_ready.load(Ordering::Acquire) {
+            // Open documents may belong to other folders now
+            self.recheck_all().await;
+        } else {
+            self.preload_dictionaries(&added).await;
+        }
     }
 
     async fn shutdown(&self) -> RpcResult<()> {
@@ -179,6 +295,8 @@ impl LanguageServer for Backend {
             Some(doc) => doc,
             None => return Ok(None),
         };
+        let uri = &params.text_document.uri;
+        let folder = self.folders.for_uri(uri);
 
         for diag in params.context.diagnostics {
             // Only process our own diagnostics
@@ -196,9 +314,14 @@ impl LanguageServer for Backend {
             if word.is_empty() || word.contains(" ") {
                 continue;
             }
-            let cb = self.codebook.clone();
+            let cb = folder.codebook.clone();
             let inner_word = word.clone();
-            let suggestions = task::spawn_blocking(move || cb.get_suggestions(&inner_word)).await;
+            let text = doc.text.clone();
+            let file_path = doc.uri.to_file_path().unwrap_or_default();
+            let suggestions = task::spawn_blocking(move || {
+                cb.get_suggestions(&inner_word, Some(&text), file_path.to_str())
+            })
+            .await;
 
             let suggestions = match suggestions {
                 Ok(suggestions) => suggestions,
@@ -218,40 +341,79 @@ impl LanguageServer for Backend {
             }
 
             suggestions.unwrap().iter().for_each(|suggestion| {
+                // The word may be part of a larger identifier, replace all of it
+                let edit = apply_suggestion(line, start_char, end_char, suggestion);
+                let range = Range {
+                    start: Position {
+                        line: diag.range.start.line,
+                        character: edit.start_char,
+                    },
+                    end: Position {
+                        line: diag.range.end.line,
+                        character: edit.end_char,
+                    },
+                };
                 actions.push(CodeActionOrCommand::CodeAction(self.make_suggestion(
-                    suggestion,
-                    &diag.range,
+                    &edit.new_text,
+                    &range,
                     &params.text_document.uri,
                 )));
             });
-            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
-                title: format!("Add '{}' to dictionary", word),
-                kind: Some(CodeActionKind::QUICKFIX),
-                diagnostics: None,
-                edit: None,
-                command: Some(Command {
-                    title: format!("Add '{}' to dictionary", word),
-                    command: CodebookCommand::AddWord.into(),
-                    arguments: Some(vec![word.to_string().into()]),
-                }),
-                is_preferred: None,
-                disabled: None,
-                data: None,
-            }));
-            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
-                title: format!("Add '{}' to global dictionary", word),
-                kind: Some(CodeActionKind::QUICKFIX),
-                diagnostics: None,
-                edit: None,
-                command: Some(Command {
-                    title: format!("Add '{}' to global dictionary", word),
-                    command: CodebookCommand::AddWordGlobal.into(),
-                    arguments: Some(vec![word.to_string().into()]),
-                }),
-                is_preferred: None,
-                disabled: None,
-                data: None,
-            }));
+            actions.push(make_command_action(
+                format!("Add '{}' to dictionary", word),
+                CodebookCommand::AddWord,
+                &[&word],
+                Some(uri),
+            ));
+            actions.push(make_command_action(
+                format!("Add '{}' to global dictionary", word),
+                CodebookCommand::AddWordGlobal,
+                &[&word],
+                Some(uri),
+            ));
+            actions.push(CodeActionOrCommand::CodeAction(
+                self.make_directive_action(&word, &doc),
+            ));
+            for path in folder.config.get_word_list_paths() {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                actions.push(make_command_action(
+                    format!("Add '{}' to {}", word, name),
+                    CodebookCommand::AddWordToList,
+                    &[&word, &path.to_string_lossy()],
+                    None,
+                ));
+            }
+        }
+        if !actions.is_empty() {
+            actions.push(make_command_action(
+                "Add all unknown words in this file to dictionary".to_string(),
+                CodebookCommand::AddUnknownWords,
+                &[uri.as_str()],
+                None,
+            ));
+            actions.push(make_command_action(
+                "Ignore this file".to_string(),
+                CodebookCommand::IgnoreFile,
+                &[uri.as_str()],
+                None,
+            ));
+        } else if let Some(word) = self.word_at(&doc.text, params.range.start) {
+            // Correct words can be flagged, or removed again if they were added
+            if folder.config.is_added_word(&word) {
+                actions.push(make_command_action(
+                    format!("Remove '{}' from dictionary", word),
+                    CodebookCommand::RemoveWord,
+                    &[&word],
+                    Some(uri),
+                ));
+            } else if !folder.config.should_flag_word(&word) {
+                actions.push(make_command_action(
+                    format!("Flag '{}' as misspelled", word),
+                    CodebookCommand::FlagWord,
+                    &[&word],
+                    Some(uri),
+                ));
+            }
         }
         match actions.is_empty() {
             true => Ok(None),
@@ -259,7 +421,67 @@ impl LanguageServer for Backend {
         }
     }
 
+    async fn completion(&self, params: CompletionParams) -> RpcResult<Option<CompletionResponse>> {
+        let position = params.text_document_position;
+        let Some(doc) = self.config_document(&position.text_document.uri) else {
+            return Ok(None);
+        };
+        let items: Vec<CompletionItem> = match completion_at(
+            &doc.text,
+            position.position.line,
+            position.position.character,
+        ) {
+            ConfigCompletion::Keys(settings) => settings
+                .iter()
+                .map(|setting| CompletionItem {
+                    label: setting.key.to_string(),
+                    kind: Some(CompletionItemKind::PROPERTY),
+                    documentation: Some(setting_documentation(setting)),
+                    ..Default::default()
+                })
+                .collect(),
+            ConfigCompletion::Dictionaries { quoted } => get_dictionary_ids()
+                .into_iter()
+                .map(|id| CompletionItem {
+                    insert_text: (!quoted).then(|| format!("\"{}\"", id)),
+                    label: id,
+                    kind: Some(CompletionItemKind::VALUE),
+                    ..Default::default()
+                })
+                .collect(),
+            ConfigCompletion::None => return Ok(None),
+        };
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    async fn hover(&self, params: HoverParams) -> RpcResult<Option<Hover>> {
+        let position = params.text_document_position_params;
+        // Keys of config files are documented, flagged words anywhere explained
+        if let Some(doc) = self.config_document(&position.text_document.uri) {
+            let setting = setting_at(
+                &doc.text,
+                position.position.line,
+                position.position.character,
+            );
+            if let Some(setting) = setting {
+                return Ok(Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: format!("`{}`\n\n{}", setting.key, setting.description),
+                    }),
+                    range: None,
+                }));
+            }
+        }
+        Ok(self
+            .flagged_word_hover(&position.text_document.uri, position.position)
+            .await)
+    }
+
     async fn execute_command(&self, params: ExecuteCommandParams) -> RpcResult<Option<Value>> {
+        // Other folders may have saved the global config
+        self.reload_configs().await;
+        let folder = self.command_folder(&params.arguments);
         match CodebookCommand::from(params.command.as_str()) {
             CodebookCommand::AddWord => {
                 let words = params
@@ -270,9 +492,9 @@ impl LanguageServer for Backend {
                     "Adding words to dictionary {}",
                     words.clone().collect::<Vec<String>>().join(", ")
                 );
-                let updated = self.add_words(words);
+                let updated = self.add_words(&folder, words);
                 if updated {
-                    let _ = self.config.save();
+                    let _ = folder.config.save();
                     self.recheck_all().await;
                 }
                 Ok(None)
@@ -282,13 +504,132 @@ impl LanguageServer for Backend {
                     .arguments
                     .iter()
                     .filter_map(|arg| arg.as_str().map(|s| s.to_string()));
-                let updated = self.add_words_global(words);
+                let updated = self.add_words_global(&folder, words);
                 if updated {
-                    let _ = self.config.save_global();
+                    let _ = folder.config.save_global();
+                    self.recheck_all().await;
+                }
+                Ok(None)
+            }
+            CodebookCommand::RemoveWord => {
+                let words: Vec<String> = params
+                    .arguments
+                    .iter()
+                    .filter_map(|arg| arg.as_str().map(|s| s.to_string()))
+                    .collect();
+                info!("Removing words from dictionary {}", words.join(", "));
+                let removed = self.update_words(&words, |word| folder.config.remove_word(word));
+                let removed_global =
+                    self.update_words(&words, |word| folder.config.remove_word_global(word));
+                if removed {
+                    let _ = folder.config.save();
+                }
+                if removed_global {
+                    let _ = folder.config.save_global();
+                }
+                if removed || removed_global {
+                    self.recheck_all().await;
+                }
+                Ok(None)
+            }
+            CodebookCommand::IgnoreFile => {
+                let mut updated = Vec::new();
+                for uri in params.arguments.iter().filter_map(|arg| arg.as_str()) {
+                    let Some(path) = Url::parse(uri).ok().and_then(|u| u.to_file_path().ok())
+                    else {
+                        error!("Can't ignore '{}', it isn't a file URI", uri);
+                        continue;
+                    };
+                    info!("Ignoring file {}", path.display());
+                    let folder = self.folders.for_path(&path);
+                    match folder.config.add_ignore_file(&path) {
+                        Ok(true) => updated.push(folder),
+                        Ok(false) => {}
+                        Err(e) => error!("Failed to ignore file: {}", e),
+                    }
+                }
+                for folder in &updated {
+                    let _ = folder.config.save();
+                }
+                if !updated.is_empty() {
+                    self.recheck_all().await;
+                }
+                Ok(None)
+            }
+            CodebookCommand::FlagWord => {
+                let words: Vec<String> = params
+                    .arguments
+                    .iter()
+                    .filter_map(|arg| arg.as_str().map(|s| s.to_string()))
+                    .collect();
+                info!("Flagging words {}", words.join(", "));
+                if self.update_words(&words, |word| folder.config.flag_word(word)) {
+                    let _ = folder.config.save();
                     self.recheck_all().await;
                 }
                 Ok(None)
             }
+            CodebookCommand::AddWordToList => {
+                let (Some(word), Some(path)) = (
+                    params.arguments.first().and_then(|arg| arg.as_str()),
+                    params.arguments.get(1).and_then(|arg| arg.as_str()),
+                ) else {
+                    error!("Expected a word and a word list path");
+                    return Ok(None);
+                };
+                let folder = self.folders.for_path(Path::new(path));
+                match folder.config.add_word_to_list(word, Path::new(path)) {
+                    Ok(true) => self.recheck_all().await,
+                    Ok(false) => info!("Word '{}' already exists in {}.", word, path),
+                    Err(e) => error!("Failed to add word to {}: {}", path, e),
+                }
+                Ok(None)
+            }
+            CodebookCommand::CheckWorkspace => {
+                if !self.dictionaries_ready.load(Ordering::Acquire) {
+                    self.client
+                        .show_message(
+                            MessageType::INFO,
+                            "Dictionaries are still loading, check the workspace once they are ready.",
+                        )
+                        .await;
+                    return Ok(None);
+                }
+                let summary = self.check_workspace(&self.folders.all()).await;
+                Ok(serde_json::to_value(summary.finish(SUMMARY_WORD_LIMIT)).ok())
+            }
+            CodebookCommand::AddUnknownWords => {
+                if !self.dictionaries_ready.load(Ordering::Acquire) {
+                    self.client
+                        .show_message(
+                            MessageType::INFO,
+                            "Dictionaries are still loading, add unknown words once they are ready.",
+                        )
+                        .await;
+                    return Ok(None);
+                }
+                let options = match params.arguments.first() {
+                    Some(Value::String(uri)) => Url::parse(uri)
+                        .map(|uri| AddUnknownWordsOptions {
+                            uri: Some(uri),
+                            ..Default::default()
+                        })
+                        .map_err(|e| e.to_string()),
+                    Some(options) => {
+                        serde_json::from_value(options.clone()).map_err(|e| e.to_string())
+                    }
+                    None => Ok(AddUnknownWordsOptions::default()),
+                };
+                let options = match options {
+                    Ok(options) => options,
+                    Err(e) => {
+                        error!("Invalid options for adding unknown words: {}", e);
+                        return Ok(None);
+                    }
+                };
+                let added = self.add_unknown_words(&options).await;
+                Ok(Some(json!({ "added": added })))
+            }
             CodebookCommand::Unknown => Ok(None),
         }
     }
@@ -296,20 +637,300 @@ impl LanguageServer for Backend {
 
 impl Backend {
     pub fn new(client: Client, workspace_dir: &Path) -> Self {
-        let config = CodebookConfig::load(Some(workspace_dir)).expect("Unable to make config.");
-        let config_arc = Arc::new(config);
-        let cb_config = Arc::clone(&config_arc);
-        let codebook = Arc::new(Codebook::new(cb_config).expect("Unable to make codebook"));
+        let folder = Folder::load(workspace_dir, None).expect("Unable to make codebook.");
 
         Self {
             client,
-            codebook,
-            config: Arc::clone(&config_arc),
+            folders: WorkspaceFolders::new(folder),
             document_cache: TextDocumentCache::default(),
+            dictionaries_ready: AtomicBool::new(false),
+            supports_progress: AtomicBool::new(false),
+        }
+    }
+
+    /// Load a workspace folder and add it, unless it was added before
+    async fn add_folder(&self, root: PathBuf) -> Option<Arc<Folder>> {
+        if self.folders.contains_root(&root) {
+            return None;
         }
+        let folder = match self.folders.reuse_default(&root) {
+            Some(folder) => folder,
+            None => {
+                let shared = self.folders.default_codebook();
+                let loading = task::spawn_blocking(move || {
+                    Folder::load(&root, Some(&shared)).map_err(|e| e.to_string())
+                });
+                match loading.await {
+                    Ok(Ok(folder)) => folder,
+                    Ok(Err(e)) => {
+                        error!("Failed to load workspace folder: {}", e);
+                        return None;
+                    }
+                    Err(e) => {
+                        error!("Failed to load workspace folder: {}", e);
+                        return None;
+                    }
+                }
+            }
+        };
+        let folder = Arc::new(folder);
+        self.folders.add(Arc::clone(&folder));
+        Some(folder)
     }
-    fn make_diagnostic(&self, result: &WordLocation, range: &TextRange) -> Diagnostic {
-        let message = format!("Possible spelling issue '{}'.", result.word);
+
+    /// Load the dictionaries of workspace folders in the background, reporting
+    /// progress to the client, then check the documents opened in the meantime.
+    async fn preload_dictionaries(&self, folders: &[Arc<Folder>]) {
+        let token = NumberOrString::String(LOAD_PROGRESS_TOKEN.to_string());
+        let show_progress = self.begin_progress(&token, "Loading dictionaries").await;
+
+        for (folder_index, folder) in folders.iter().enumerate() {
+            let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+            let cb = folder.codebook.clone();
+            let loading = task::spawn_blocking(move || {
+                cb.preload_dictionaries(|id, index, total| {
+                    let _ = sender.send((id.to_string(), index, total));
+                })
+            });
+            // Ends when loading is done and the sender is dropped
+            while let Some((id, index, total)) = receiver.recv().await {
+                if show_progress {
+                    let done = folder_index * total.max(1) + index;
+                    let percentage = done * 100 / (folders.len() * total.max(1));
+                    self.send_progress(
+                        &token,
+                        WorkDoneProgress::Report(WorkDoneProgressReport {
+                            cancellable: Some(false),
+                            message: Some(id),
+                            percentage: Some(percentage as u32),
+                        }),
+                    )
+                    .await;
+                }
+            }
+            match loading.await {
+                Ok(results) => {
+                    for (id, _) in results.iter().filter(|(_, loaded)| !loaded) {
+                        info!("Dictionary not available: {}", id);
+                    }
+                }
+                Err(e) => error!("Failed to load dictionaries: {}", e),
+            }
+        }
+
+        if show_progress {
+            self.send_progress(
+                &token,
+                WorkDoneProgress::End(WorkDoneProgressEnd { message: None }),
+            )
+            .await;
+        }
+        self.dictionaries_ready.store(true, Ordering::Release);
+        self.recheck_all().await;
+    }
+
+    /// Spell check every file of workspace folders, publishing diagnostics for
+    /// each, and count the unknown words so they can be added in bulk.
+    async fn check_workspace(&self, folders: &[Arc<Folder>]) -> WorkspaceSummary {
+        let roots: Vec<_> = folders
+            .iter()
+            .map(|folder| (folder.root.clone(), folder.config.clone()))
+            .collect();
+        let folder_files = task::spawn_blocking(move || {
+            roots
+                .into_iter()
+                .map(|(root, config)| workspace_files(&root, config))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to list workspace files: {}", e);
+            Vec::new()
+        });
+        // Files of nested folders are checked with the innermost folder
+        let files: Vec<(&Arc<Folder>, PathBuf)> = folders
+            .iter()
+            .zip(folder_files)
+            .flat_map(|(folder, files)| files.into_iter().map(move |path| (folder, path)))
+            .filter(|(folder, path)| Arc::ptr_eq(&self.folders.for_path(path), folder))
+            .collect();
+        info!("Checking {} workspace files", files.len());
+
+        let token = NumberOrString::String(CHECK_PROGRESS_TOKEN.to_string());
+        let show_progress = self.begin_progress(&token, "Checking workspace").await;
+        let mut summary = WorkspaceSummary::default();
+        for (index, (folder, path)) in files.iter().enumerate() {
+            if show_progress {
+                let relative = path.strip_prefix(&folder.root).unwrap_or(path);
+                self.send_progress(
+                    &token,
+                    WorkDoneProgress::Report(WorkDoneProgressReport {
+                        cancellable: Some(false),
+                        message: Some(relative.display().to_string()),
+                        percentage: Some((index * 100 / files.len()) as u32),
+                    }),
+                )
+                .await;
+            }
+            let Ok(uri) = Url::from_file_path(path) else {
+                continue;
+            };
+            // Open documents may have unsaved changes
+            let Some(doc) = self.document_or_file(&uri) else {
+                continue;
+            };
+            let results = self.publish_document_diagnostics(doc).await;
+            summary.add_file(&results);
+        }
+        if show_progress {
+            self.send_progress(
+                &token,
+                WorkDoneProgress::End(WorkDoneProgressEnd {
+                    message: Some(format!(
+                        "{} issues in {} files",
+                        summary.issues, summary.files_with_issues
+                    )),
+                }),
+            )
+            .await;
+        }
+        summary
+    }
+
+    /// Add the unknown words of a file or the workspace to the config in one
+    /// batch, returning the added words.
+    async fn add_unknown_words(&self, options: &AddUnknownWordsOptions) -> Vec<String> {
+        let mut added = Vec::new();
+        match &options.uri {
+            Some(uri) => {
+                let Some(doc) = self.document_or_file(uri) else {
+                    error!("Can't add unknown words of '{}', it can't be read", uri);
+                    return Vec::new();
+                };
+                let mut summary = WorkspaceSummary::default();
+                summary.add_file(&self.publish_document_diagnostics(doc).await);
+                added = self.add_summary_words(&self.folders.for_uri(uri), &summary, options);
+            }
+            // Each folder gets the words of its own files
+            None => {
+                for folder in self.folders.all() {
+                    let summary = self.check_workspace(&[Arc::clone(&folder)]).await;
+                    added.extend(self.add_summary_words(&folder, &summary, options));
+                }
+            }
+        }
+        if added.is_empty() {
+            return added;
+        }
+        added.sort();
+        added.dedup();
+        self.recheck_all().await;
+        if options.uri.is_none() {
+            // Closed files of the workspace have diagnostics too
+            self.check_workspace(&self.folders.all()).await;
+        }
+        added
+    }
+
+    /// Add the unknown words counted in a summary to the config of a folder
+    /// and save it once, returning the added words.
+    fn add_summary_words(
+        &self,
+        folder: &Folder,
+        summary: &WorkspaceSummary,
+        options: &AddUnknownWordsOptions,
+    ) -> Vec<String> {
+        // Another folder may have just saved the global config
+        if let Err(e) = folder.config.reload() {
+            error!("Failed to reload config: {}", e);
+        }
+        let words = summary.unknown_words(options.min_count);
+        info!(
+            "Adding {} unknown words of {}",
+            words.len(),
+            folder.root.display()
+        );
+        let words: Vec<&str> = words.iter().map(|word| word.word.as_str()).collect();
+        let added = match options.global {
+            true => folder.config.add_words_global(&words),
+            false => folder.config.add_words(&words),
+        };
+        let added = added
+            .inspect_err(|e| error!("Failed to add words: {}", e))
+            .unwrap_or_default();
+        if added.is_empty() {
+            return added;
+        }
+        let saved = match options.global {
+            true => folder.config.save_global(),
+            false => folder.config.save(),
+        };
+        if let Err(e) = saved {
+            error!("Failed to save config: {}", e);
+        }
+        added
+    }
+
+    /// The open document at `uri`, or the file on disk if it isn't open
+    fn document_or_file(&self, uri: &Url) -> Option<TextDocumentCacheItem> {
+        if let Some(doc) = self.document_cache.get(uri.as_ref()) {
+            return Some(doc);
+        }
+        let path = uri.to_file_path().ok()?;
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Some(TextDocumentCacheItem::new(uri, None, None, Some(&text))),
+            Err(e) => {
+                debug!("Can't read {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Create a progress token and begin reporting with it, returning whether
+    /// the client accepted it.
+    async fn begin_progress(&self, token: &NumberOrString, title: &str) -> bool {
+        let accepted = self.supports_progress.load(Ordering::Relaxed)
+            && self
+                .client
+                .send_request::<request::WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                    token: token.clone(),
+                })
+                .await
+                .inspect_err(|e| debug!("Client refused progress token: {}", e))
+                .is_ok();
+        if accepted {
+            self.send_progress(
+                token,
+                WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                    title: title.to_string(),
+                    cancellable: Some(false),
+                    message: None,
+                    percentage: Some(0),
+                }),
+            )
+            .await;
+        }
+        accepted
+    }
+
+    async fn send_progress(&self, token: &NumberOrString, progress: WorkDoneProgress) {
+        self.client
+            .send_notification::<notification::Progress>(ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(progress),
+            })
+            .await;
+    }
+    fn make_diagnostic(
+        &self,
+        result: &WordLocation,
+        casing: Option<&str>,
+        range: &TextRange,
+    ) -> Diagnostic {
+        let message = match casing {
+            Some(casing) => format!("'{}' should be written '{}'.", result.word, casing),
+            None => format!("Possible spelling issue '{}'.", result.word),
+        };
         Diagnostic {
             range: Range {
                 start: Position {
@@ -332,10 +953,10 @@ impl Backend {
         }
     }
 
-    fn add_words(&self, words: impl Iterator<Item = String>) -> bool {
+    fn add_words(&self, folder: &Folder, words: impl Iterator<Item = String>) -> bool {
         let mut should_save = false;
         for word in words {
-            match self.config.add_word(&word) {
+            match folder.config.add_word(&word) {
                 Ok(true) => {
                     should_save = true;
                 }
@@ -349,10 +970,10 @@ impl Backend {
         }
         should_save
     }
-    fn add_words_global(&self, words: impl Iterator<Item = String>) -> bool {
+    fn add_words_global(&self, folder: &Folder, words: impl Iterator<Item = String>) -> bool {
         let mut should_save = false;
         for word in words {
-            match self.config.add_word_global(&word) {
+            match folder.config.add_word_global(&word) {
                 Ok(true) => {
                     should_save = true;
                 }
@@ -367,6 +988,156 @@ impl Backend {
         should_save
     }
 
+    /// The folder whose config a command changes: the folder of the document
+    /// passed as `{ "uri": ... }`, or the first workspace folder
+    fn command_folder(&self, arguments: &[Value]) -> Arc<Folder> {
+        arguments
+            .iter()
+            .find_map(|arg| arg.get("uri")?.as_str())
+            .and_then(|uri| Url::parse(uri).ok())
+            .map(|uri| self.folders.for_uri(&uri))
+            .unwrap_or_else(|| self.folders.all().remove(0))
+    }
+
+    /// Reload the config of every folder, true if any changed
+    async fn reload_configs(&self) -> bool {
+        let folders = self.folders.all();
+        let reloading = task::spawn_blocking(move || {
+            let mut changed = false;
+            for folder in folders {
+                match folder.config.reload() {
+                    Ok(did_reload) => changed |= did_reload,
+                    Err(e) => error!("Failed to reload config: {}", e),
+                }
+            }
+            changed
+        });
+        reloading.await.unwrap_or_else(|e| {
+            error!("Failed to reload configs: {}", e);
+            false
+        })
+    }
+
+    /// Apply a config change to each word, true if any changed the config
+    fn update_words(
+        &self,
+        words: &[String],
+        update: impl Fn(&str) -> Result<bool, std::io::Error>,
+    ) -> bool {
+        let mut should_save = false;
+        for word in words {
+            match update(word) {
+                Ok(changed) => should_save |= changed,
+                Err(e) => error!("Failed to update word '{}': {}", word, e),
+            }
+        }
+        should_save
+    }
+
+    /// The word at a position of a document, for actions on correct words
+    fn word_at(&self, text: &str, position: Position) -> Option<String> {
+        let line = text.lines().nth(position.line as usize)?;
+        get_word_at(position.character as usize, line)
+    }
+
+    /// Explain the flagged word at a position, if there is one
+    async fn flagged_word_hover(&self, uri: &Url, position: Position) -> Option<Hover> {
+        if !self.dictionaries_ready.load(Ordering::Acquire) {
+            return None;
+        }
+        let doc = self.document_cache.get(uri.as_ref())?;
+        // The results of the last check, none if the document changed since
+        let (word, range) = doc.spell_results.as_ref()?.iter().find_map(|location| {
+            let range = location.locations.iter().find(|range| {
+                range.line == position.line
+                    && (range.start_char..=range.end_char).contains(&position.character)
+            })?;
+            Some((location.word.clone(), *range))
+        })?;
+        let file_path = doc.uri.to_file_path().unwrap_or_default();
+        let lang_type = doc
+            .language_id
+            .as_deref()
+            .and_then(|lang| LanguageType::from_str(lang).ok());
+        let cb = self.folders.for_uri(uri).codebook.clone();
+        let result = task::spawn_blocking(move || {
+            let fp = file_path.to_str().unwrap_or_default();
+            let line = doc
+                .text
+                .lines()
+                .nth(range.line as usize)
+                .unwrap_or_default();
+            let identifier =
+                get_identifier_at(range.start_char as usize, line).unwrap_or_else(|| word.clone());
+            let explanation =
+                cb.explain_word(&word, &identifier, lang_type, Some(&doc.text), Some(fp));
+            (word, identifier, range, explanation)
+        })
+        .await;
+        let (word, identifier, range, explanation) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                error!("Failed to explain word in '{}': {}", uri, e);
+                return None;
+            }
+        };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: explanation_markdown(&word, &identifier, &explanation),
+            }),
+            range: Some(Range {
+                start: Position {
+                    line: range.line,
+                    character: range.start_char,
+                },
+                end: Position {
+                    line: range.line,
+                    character: range.end_char,
+                },
+            }),
+        })
+    }
+
+    /// Add a word with a `codebook:words` directive in the document itself
+    fn make_directive_action(&self, word: &str, doc: &TextDocumentCacheItem) -> CodeAction {
+        let language = doc
+            .language_id
+            .as_deref()
+            .and_then(|lang| LanguageType::from_str(lang).ok())
+            .unwrap_or(LanguageType::Text);
+        let edit = add_word_directive(&doc.text, language, word);
+        let position = Position {
+            line: edit.line,
+            character: edit.character,
+        };
+        let mut map = HashMap::new();
+        map.insert(
+            doc.uri.clone(),
+            vec![TextEdit {
+                range: Range {
+                    start: position,
+                    end: position,
+                },
+                new_text: edit.new_text,
+            }],
+        );
+        CodeAction {
+            title: format!("Add '{}' to file-local word list", word),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: None,
+            edit: Some(WorkspaceEdit {
+                changes: Some(map),
+                document_changes: None,
+                change_annotations: None,
+            }),
+            command: None,
+            is_preferred: None,
+            disabled: None,
+            data: None,
+        }
+    }
+
     fn make_suggestion(&self, suggestion: &str, range: &Range, uri: &Url) -> CodeAction {
         let title = format!("Replace with '{}'", suggestion);
         let mut map = HashMap::new();
@@ -403,7 +1174,7 @@ impl Backend {
     }
 
     async fn spell_check(&self, uri: &Url) {
-        let did_reload = match self.config.reload() {
+        let did_reload = match self.folders.for_uri(uri).config.reload() {
             Ok(did_reload) => did_reload,
             Err(e) => {
                 error!("Failed to reload config: {}", e);
@@ -418,12 +1189,31 @@ impl Backend {
         }
     }
 
+    /// The open document at `uri` if it is a config file
+    fn config_document(&self, uri: &Url) -> Option<TextDocumentCacheItem> {
+        let doc = self.document_cache.get(uri.as_ref())?;
+        let file_path = doc.uri.to_file_path().ok()?;
+        let folder = self.folders.for_path(&file_path);
+        folder.config.is_config_file(&file_path).then_some(doc)
+    }
+
     /// Helper method to publish diagnostics for spell-checking.
     async fn publish_spellcheck_diagnostics(&self, uri: &Url) {
+        if !self.dictionaries_ready.load(Ordering::Acquire) {
+            // Checked once the dictionaries are loaded
+            return;
+        }
         let doc = match self.document_cache.get(uri.as_ref()) {
             Some(doc) => doc,
             None => return,
         };
+        self.publish_document_diagnostics(doc).await;
+    }
+
+    /// Spell check a document and publish its diagnostics, returning the
+    /// unknown words, not those flagged by the config or only written with
+    /// the wrong casing.
+    async fn publish_document_diagnostics(&self, doc: TextDocumentCacheItem) -> Vec<WordLocation> {
         // Convert the file URI to a local file path.
         let file_path = doc.uri.to_file_path().unwrap_or_default();
         debug!("Spell-checking file: {:?}", file_path);
@@ -433,37 +1223,66 @@ impl Backend {
             .as_deref()
             .and_then(|lang| LanguageType::from_str(lang).ok());
 
-        let cb = self.codebook.clone();
+        // Config files also get their settings validated
+        let folder = self.folders.for_path(&file_path);
+        let config_issues = if folder.config.is_config_file(&file_path) {
+            let is_global = folder.config.global_config_path.as_deref() == Some(&file_path);
+            validate_config(&doc.text, is_global, |id| get_repo(id).is_some())
+        } else {
+            Vec::new()
+        };
+
+        let cb = folder.codebook.clone();
         let fp = file_path.clone();
+        let text = doc.text;
         let spell_results = task::spawn_blocking(move || {
-            cb.spell_check(&doc.text, lang_type, Some(fp.to_str().unwrap_or_default()))
+            let fp = fp.to_str().unwrap_or_default();
+            let results = cb
+                .spell_check(&text, lang_type, Some(fp))
+                .into_iter()
+                .map(|res| {
+                    let casing = cb.get_correct_casing(&res.word, Some(fp));
+                    let flagged = cb.should_flag_word(&res.word, Some(fp));
+                    (res, casing, flagged)
+                })
+                .collect::<Vec<_>>();
+            (text, results)
         })
         .await;
 
         let spell_results = match spell_results {
-            Ok(results) => results,
+            Ok((text, results)) => {
+                // Hovers explain the flagged words without checking again
+                self.document_cache.set_spell_results(
+                    &doc.uri,
+                    &text,
+                    results.iter().map(|(res, _, _)| res.clone()).collect(),
+                );
+                results
+            }
             Err(err) => {
                 error!(
                     "Spell-checking failed for file '{:?}' \n Error: {}",
                     file_path, err
                 );
-                return;
+                return Vec::new();
             }
         };
 
         // 2) Convert the results to LSP diagnostics.
-        let diagnostics: Vec<Diagnostic> = spell_results
-            .into_iter()
-            .flat_map(|res| {
+        let mut diagnostics: Vec<Diagnostic> = spell_results
+            .iter()
+            .flat_map(|(res, casing, _)| {
                 // For each misspelling, create a diagnostic for each location.
                 let mut new_locations = vec![];
                 for loc in &res.locations {
-                    let diagnostic = self.make_diagnostic(&res, loc);
+                    let diagnostic = self.make_diagnostic(res, casing.as_deref(), loc);
                     new_locations.push(diagnostic);
                 }
                 new_locations
             })
             .collect();
+        diagnostics.extend(config_issues.iter().map(make_config_diagnostic));
 
         // debug!("Diagnostics: {:?}", diagnostics);
         // 3) Send the diagnostics to the client.
@@ -471,5 +1290,102 @@ impl Backend {
             .publish_diagnostics(doc.uri, diagnostics, None)
             .await;
         // debug!("Published diagnostics for: {:?}", file_path);
+        spell_results
+            .into_iter()
+            .filter(|(_, casing, flagged)| casing.is_none() && !flagged)
+            .map(|(res, _, _)| res)
+            .collect()
+    }
+}
+
+/// A code action running a command. Commands on words also get the document
+/// they are run from, as `{ "uri": ... }`, to update the config of its folder.
+fn make_command_action(
+    title: String,
+    command: CodebookCommand,
+    arguments: &[&str],
+    document: Option<&Url>,
+) -> CodeActionOrCommand {
+    let arguments = arguments
+        .iter()
+        .map(|&argument| argument.into())
+        .chain(document.map(|uri| json!({ "uri": uri })))
+        .collect();
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.clone(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: None,
+        edit: None,
+        command: Some(Command {
+            title,
+            command: command.into(),
+            arguments: Some(arguments),
+        }),
+        is_preferred: None,
+        disabled: None,
+        data: None,
+    })
+}
+
+fn explanation_markdown(word: &str, identifier: &str, explanation: &WordExplanation) -> String {
+    let code_list = |words: &[String]| {
+        words
+            .iter()
+            .map(|word| format!("`{}`", word))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut lines = vec![format!("**{}** is flagged by {}", word, SOURCE_NAME)];
+    if identifier != word {
+        lines.push(format!(
+            "- `{}` is split into {}",
+            identifier,
+            code_list(&explanation.parts)
+        ));
+    }
+    if explanation.flagged {
+        lines.push("- Matches `flag_words` in the config".to_string());
+    }
+    if let Some(casing) = &explanation.correct_casing {
+        lines.push(format!("- Configured to be written `{}`", casing));
+    }
+    if !explanation.flagged && explanation.correct_casing.is_none() {
+        lines.push(format!(
+            "- Not found in {}",
+            code_list(&explanation.dictionaries)
+        ));
+    }
+    if !explanation.suggestions.is_empty() {
+        lines.push(format!(
+            "- Suggestions: {}",
+            code_list(&explanation.suggestions)
+        ));
+    }
+    lines.join("\n")
+}
+
+fn setting_documentation(setting: &SettingInfo) -> Documentation {
+    Documentation::MarkupContent(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: setting.description.to_string(),
+    })
+}
+
+fn make_config_diagnostic(issue: &ConfigIssue) -> Diagnostic {
+    Diagnostic {
+        range: Range {
+            start: Position {
+                line: issue.start_line,
+                character: issue.start_char,
+            },
+            end: Position {
+                line: issue.end_line,
+                character: issue.end_char,
+            },
+        },
+        severity: Some(DiagnosticSeverity::WARNING),
+        source: Some(CONFIG_SOURCE_NAME.to_string()),
+        message: issue.message.clone(),
+        ..Default::default()
     }
 }
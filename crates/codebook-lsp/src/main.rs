@@ -1,14 +1,24 @@
 mod file_cache;
+mod folders;
 mod lsp;
 mod lsp_logger;
+mod workspace;
 
 use clap::{Parser, Subcommand};
+use codebook::Codebook;
+use codebook::dictionaries::repo::get_dictionary_ids;
 use codebook_config::CodebookConfig;
+use codebook_config::schema::config_schema;
+use codebook_downloader::Downloader;
 use log::info;
 use lsp::Backend;
 use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::task;
 use tower_lsp::{LspService, Server};
+use workspace::{WorkspaceSummary, workspace_files};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None, arg_required_else_help = true)]
@@ -28,6 +38,41 @@ enum Commands {
     Serve {},
     /// Remove server cache
     Clean {},
+    /// Inspect and manage downloaded dictionaries
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+    /// Print the JSON Schema of codebook.toml, for editors with TOML schema support
+    Schema {},
+    /// Add the unknown words of files, or of the whole workspace, to the config
+    AddUnknownWords {
+        /// Files to take the words from, all files of the workspace if none are given
+        files: Vec<PathBuf>,
+        /// Only add words appearing at least this many times
+        #[arg(long, default_value_t = 1)]
+        min_count: usize,
+        /// Add the words to the global config instead of the project config
+        #[arg(long)]
+        global: bool,
+        /// List the words without adding them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum CacheCommands {
+    /// List downloaded files
+    List {},
+    /// Download all dictionaries the configuration may need, e.g. before going offline
+    Prefetch {},
+    /// Remove downloaded files that haven't been used recently, and leftovers of interrupted downloads
+    Prune {
+        /// Remove files not used for this many days
+        #[arg(long, default_value_t = 30)]
+        days: u64,
+    },
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -44,10 +89,36 @@ async fn main() {
             serve_lsp(root).await;
         }
         Some(Commands::Clean {}) => {
-            let config = CodebookConfig::default();
+            // Use the configured cache dir, if the config can be read
+            let config = CodebookConfig::load(Some(root)).unwrap_or_default();
             info!("Cleaning: {:?}", config.cache_dir);
             config.clean_cache()
         }
+        Some(Commands::Cache { command }) => {
+            // Downloads block, so keep them off the async runtime
+            let (root, command) = (root.to_owned(), command.clone());
+            task::spawn_blocking(move || run_cache_command(&root, &command))
+                .await
+                .unwrap();
+        }
+        Some(Commands::Schema {}) => {
+            let schema = config_schema(&get_dictionary_ids());
+            println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+        }
+        Some(Commands::AddUnknownWords {
+            files,
+            min_count,
+            global,
+            dry_run,
+        }) => {
+            let (root, files) = (root.to_owned(), files.clone());
+            let (min_count, global, dry_run) = (*min_count, *global, *dry_run);
+            task::spawn_blocking(move || {
+                add_unknown_words(&root, files, min_count, global, dry_run)
+            })
+            .await
+            .unwrap();
+        }
         None => {}
     }
 }
@@ -65,3 +136,163 @@ async fn serve_lsp(root: &Path) {
 
     Server::new(stdin, stdout, socket).serve(service).await;
 }
+
+fn run_cache_command(root: &Path, command: &CacheCommands) {
+    let config = match CodebookConfig::load(Some(root)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load config: {}", e);
+            exit(1);
+        }
+    };
+    match command {
+        CacheCommands::List {} => {
+            for entry in open_downloader(&config).entries() {
+                let size = entry
+                    .size
+                    .map_or_else(|| "missing".to_string(), |size| size.to_string());
+                println!("{}", entry.url);
+                println!("  path:         {}", entry.path.display());
+                println!("  size:         {}", size);
+                println!("  last checked: {}", entry.last_checked.to_rfc3339());
+                println!("  last used:    {}", entry.last_used.to_rfc3339());
+                println!("  hash:         {}", entry.content_hash);
+            }
+        }
+        CacheCommands::Prefetch {} => {
+            let codebook = match Codebook::new(Arc::new(config)) {
+                Ok(codebook) => codebook,
+                Err(e) => {
+                    eprintln!("Failed to load codebook: {}", e);
+                    exit(1);
+                }
+            };
+            let mut failed = false;
+            for (id, loaded) in codebook.prefetch_dictionaries() {
+                println!("{} {}", if loaded { "ok    " } else { "failed" }, id);
+                failed |= !loaded;
+            }
+            if failed {
+                exit(1);
+            }
+        }
+        CacheCommands::Prune { days } => {
+            let max_age = Duration::from_secs(days * 24 * 3600);
+            let downloader = open_downloader(&config);
+            match downloader.prune(max_age) {
+                Ok(removed) => {
+                    for url in &removed {
+                        println!("Removed {}", url);
+                    }
+                    println!("Removed {} file(s)", removed.len());
+                }
+                Err(e) => {
+                    eprintln!("Failed to prune cache: {}", e);
+                    exit(1);
+                }
+            }
+            match downloader.collect_garbage() {
+                Ok(removed) => println!("Removed {} leftover file(s)", removed),
+                Err(e) => {
+                    eprintln!("Failed to remove leftover files: {}", e);
+                    exit(1);
+                }
+            }
+        }
+    }
+}
+
+fn add_unknown_words(
+    root: &Path,
+    files: Vec<PathBuf>,
+    min_count: usize,
+    global: bool,
+    dry_run: bool,
+) {
+    let config = match CodebookConfig::load(Some(root)) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("Failed to load config: {}", e);
+            exit(1);
+        }
+    };
+    let codebook = match Codebook::new(config.clone()) {
+        Ok(codebook) => codebook,
+        Err(e) => {
+            eprintln!("Failed to load codebook: {}", e);
+            exit(1);
+        }
+    };
+    let files = match files.is_empty() {
+        true => workspace_files(root, config.clone()),
+        false => files,
+    };
+    let mut summary = WorkspaceSummary::default();
+    for path in &files {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("Skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let file_path = path.to_str();
+        let results: Vec<_> = codebook
+            .spell_check(&text, None, file_path)
+            .into_iter()
+            // Flagged words and words only written with the wrong casing aren't unknown
+            .filter(|result| {
+                codebook
+                    .get_correct_casing(&result.word, file_path)
+                    .is_none()
+                    && !codebook.should_flag_word(&result.word, file_path)
+            })
+            .collect();
+        summary.add_file(&results);
+    }
+
+    let words = summary.unknown_words(min_count);
+    for word in &words {
+        println!("{:>6} {}", word.count, word.word);
+    }
+    if dry_run || words.is_empty() {
+        println!(
+            "Found {} unknown word(s) in {} file(s)",
+            words.len(),
+            summary.files
+        );
+        return;
+    }
+    let words: Vec<&str> = words.iter().map(|word| word.word.as_str()).collect();
+    let added = match global {
+        true => config.add_words_global(&words),
+        false => config.add_words(&words),
+    };
+    let added = match added {
+        Ok(added) => added.len(),
+        Err(e) => {
+            eprintln!("Failed to add words: {}", e);
+            exit(1);
+        }
+    };
+    let (saved, path) = match global {
+        true => (config.save_global(), &config.global_config_path),
+        false => (config.save(), &config.project_config_path),
+    };
+    if let Err(e) = saved {
+        eprintln!("Failed to save config: {}", e);
+        exit(1);
+    }
+    let path = path.as_deref().unwrap_or(Path::new("config"));
+    println!("Added {} word(s) to {}", added, path.display());
+}
+
+fn open_downloader(config: &CodebookConfig) -> Downloader {
+    match Downloader::with_options(&config.cache_dir, config.get_downloader_options()) {
+        Ok(downloader) => downloader,
+        Err(e) => {
+            eprintln!("Failed to open cache at {:?}: {}", config.cache_dir, e);
+            exit(1);
+        }
+    }
+}
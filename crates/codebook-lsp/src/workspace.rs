@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use codebook::parser::WordLocation;
+use codebook::queries::LANGUAGE_SETTINGS;
+use codebook_config::CodebookConfig;
+use ignore::WalkBuilder;
+use serde::Serialize;
+
+/// Extensions of prose files checked along with source files
+static TEXT_EXTENSIONS: &[&str] = &["md", "markdown", "txt", "rst"];
+
+/// Files of the workspace to spell check: source and prose files not excluded
+/// by `.gitignore` files or the `ignore_paths` setting, sorted by path.
+pub fn workspace_files(root: &Path, config: Arc<CodebookConfig>) -> Vec<PathBuf> {
+    let is_ignored = move |path: &Path| config.config_for_path(Some(path)).should_ignore_path(path);
+    let mut files: Vec<PathBuf> = WalkBuilder::new(root)
+        // Respect .gitignore files outside of repositories too
+        .require_git(false)
+        .filter_entry(move |entry| !is_ignored(entry.path()))
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(|path| is_checked_file(path))
+        .collect();
+    files.sort();
+    files
+}
+
+fn is_checked_file(path: &Path) -> bool {
+    let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    TEXT_EXTENSIONS.contains(&extension)
+        || LANGUAGE_SETTINGS
+            .iter()
+            .any(|setting| setting.extensions.contains(&extension))
+}
+
+/// An unknown word of the workspace
+#[derive(Debug, Serialize)]
+pub struct WordCount {
+    pub word: String,
+    /// Times the word appears in the workspace
+    pub count: usize,
+    /// Number of files the word appears in
+    pub files: usize,
+}
+
+/// Result of the `codebook.checkWorkspace` command
+#[derive(Debug, Default, Serialize)]
+pub struct WorkspaceSummary {
+    /// Number of files checked
+    pub files: usize,
+    /// Number of files with spelling issues
+    pub files_with_issues: usize,
+    /// Number of spelling issues across the workspace
+    pub issues: usize,
+    /// The most frequent unknown words, most frequent first
+    pub words: Vec<WordCount>,
+    #[serde(skip)]
+    counts: HashMap<String, (usize, usize)>,
+}
+
+impl WorkspaceSummary {
+    /// Count the spelling issues of a checked file. Words are counted
+    /// lowercased, as they are added to the config.
+    pub fn add_file(&mut self, results: &[WordLocation]) {
+        self.files += 1;
+        if !results.is_empty() {
+            self.files_with_issues += 1;
+        }
+        let mut seen = HashSet::new();
+        for result in results {
+            let word = result.word.to_lowercase();
+            let (count, files) = self.counts.entry(word.clone()).or_default();
+            *count += result.locations.len();
+            // "Foo" and "foo" in one file count as one file
+            if seen.insert(word) {
+                *files += 1;
+            }
+            self.issues += result.locations.len();
+        }
+    }
+
+    /// Unknown words appearing at least `min_count` times, most frequent first,
+    /// ties sorted by word
+    pub fn unknown_words(&self, min_count: usize) -> Vec<WordCount> {
+        let mut words: Vec<WordCount> = self
+            .counts
+            .iter()
+            .filter(|(_, (count, _))| *count >= min_count)
+            .map(|(word, &(count, files))| WordCount {
+                word: word.clone(),
+                count,
+                files,
+            })
+            .collect();
+        words.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+        words
+    }
+
+    /// Keep the `limit` most frequent words
+    pub fn finish(mut self, limit: usize) -> Self {
+        self.words = self.unknown_words(0);
+        self.words.truncate(limit);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codebook::parser::TextRange;
+    use std::fs;
+
+    fn result(word: &str, count: u32) -> WordLocation {
+        let locations = (0..count)
+            .map(|line| TextRange {
+                start_char: 0,
+                end_char: word.len() as u32,
+                line,
+            })
+            .collect();
+        WordLocation::new(word.to_string(), locations)
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut summary = WorkspaceSummary::default();
+        summary.add_file(&[result("Qwerty", 2), result("qwerty", 1), result("zxcv", 1)]);
+        summary.add_file(&[]);
+        summary.add_file(&[result("asdf", 3), result("QWERTY", 1)]);
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.files_with_issues, 2);
+        assert_eq!(summary.issues, 8);
+
+        let words = summary.unknown_words(2);
+        let words: Vec<(&str, usize, usize)> = words
+            .iter()
+            .map(|w| (w.word.as_str(), w.count, w.files))
+            .collect();
+        assert_eq!(words, vec![("qwerty", 4, 2), ("asdf", 3, 1)]);
+
+        let summary = summary.finish(2);
+        let words: Vec<&str> = summary.words.iter().map(|w| w.word.as_str()).collect();
+        assert_eq!(words, vec!["qwerty", "asdf"]);
+        // Ties are sorted by word
+        let mut summary = WorkspaceSummary::default();
+        summary.add_file(&[result("bbb", 1), result("aaa", 1)]);
+        let summary = summary.finish(10);
+        let words: Vec<&str> = summary.words.iter().map(|w| w.word.as_str()).collect();
+        assert_eq!(words, vec!["aaa", "bbb"]);
+    }
+
+    #[test]
+    fn test_workspace_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        for file in [
+            "README.md",
+            "src/main.rs",
+            "src/data.bin",
+            "vendor/lib.rs",
+            "build/out.rs",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "text").unwrap();
+        }
+        fs::write(root.join(".gitignore"), "build/\n").unwrap();
+        fs::write(
+            root.join("codebook.toml"),
+            "ignore_paths = [\"vendor/**\"]\n",
+        )
+        .unwrap();
+        let config = Arc::new(CodebookConfig::load(Some(root)).unwrap());
+
+        let files: Vec<PathBuf> = workspace_files(root, config)
+            .into_iter()
+            .map(|path| path.strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("README.md"),
+                PathBuf::from("codebook.toml"),
+                PathBuf::from("src/main.rs")
+            ]
+        );
+    }
+}
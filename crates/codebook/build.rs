@@ -0,0 +1,88 @@
+//! Generates the list of dictionaries embedded with the `bundled-dictionaries` feature
+//! from the repository's `dictionaries/<id>/dict` directories.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Dictionaries used by default, a build bundling dictionaries must include them
+const REQUIRED_DICTIONARIES: &[&str] = &["en_us", "software_terms", "computing_acronyms"];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("bundled.gen.rs");
+
+    let mut entries = String::new();
+    if env::var_os("CARGO_FEATURE_BUNDLED_DICTIONARIES").is_some() {
+        let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+        let dictionaries_dir = manifest_dir.join("../../dictionaries");
+        if !dictionaries_dir.is_dir() {
+            panic!(
+                "bundled-dictionaries needs the dictionaries directory at {}",
+                dictionaries_dir.display()
+            );
+        }
+        println!("cargo:rerun-if-changed={}", dictionaries_dir.display());
+        let dirs = dictionary_dirs(&dictionaries_dir);
+        for required in REQUIRED_DICTIONARIES {
+            if !dirs.iter().any(|(id, _)| id == required) {
+                panic!("Dictionary {required} isn't built, run `make build-dictionaries`");
+            }
+        }
+        for (id, dict_dir) in dirs {
+            entries.push_str(&bundled_entry(&id, &dict_dir));
+        }
+    }
+
+    let generated =
+        format!("pub static BUNDLED_DICTIONARIES: &[BundledDictionary] = &[\n{entries}];\n");
+    fs::write(out_path, generated).unwrap();
+}
+
+/// Dictionary ids and their `dict` directories, sorted by id.
+fn dictionary_dirs(dictionaries_dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut dirs: Vec<(String, PathBuf)> = fs::read_dir(dictionaries_dir)
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.join("dict").is_dir())
+        .map(|path| {
+            let id = path.file_name().unwrap().to_string_lossy().to_string();
+            (id, path.join("dict"))
+        })
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn include(path: &Path) -> String {
+    format!("include_str!({:?})", path.canonicalize().unwrap())
+}
+
+fn bundled_entry(id: &str, dict_dir: &Path) -> String {
+    let mut files: Vec<PathBuf> = fs::read_dir(dict_dir)
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    files.sort();
+
+    let hunspell = files
+        .iter()
+        .filter(|path| path.extension().is_some_and(|e| e == "dic"))
+        .map(|dic| (dic.with_extension("aff"), dic.clone()))
+        .find(|(aff, _)| aff.exists())
+        .map(|(aff, dic)| format!("Some(({}, {}))", include(&aff), include(&dic)))
+        .unwrap_or_else(|| "None".to_string());
+
+    let word_list_path = dict_dir.join("wordlist.txt");
+    let word_list = if word_list_path.exists() {
+        format!("Some({})", include(&word_list_path))
+    } else {
+        "None".to_string()
+    };
+
+    format!(
+        "    BundledDictionary {{\n        id: {id:?},\n        hunspell: {hunspell},\n        word_list: {word_list},\n    }},\n"
+    )
+}
@@ -0,0 +1,213 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, PartialEq)]
+pub enum WordCase {
+    AllCaps,
+    AllLower,
+    TitleCase,
+    Unknown,
+}
+
+impl WordCase {
+    /// Detect the casing of a word, looking only at its letters.
+    pub fn of(word: &str) -> Self {
+        let letters: Vec<char> = word.chars().filter(|c| c.is_alphabetic()).collect();
+        let Some((first, rest)) = letters.split_first() else {
+            return WordCase::Unknown;
+        };
+        if letters.iter().all(|c| c.is_uppercase()) {
+            WordCase::AllCaps
+        } else if letters.iter().all(|c| c.is_lowercase()) {
+            WordCase::AllLower
+        } else if first.is_uppercase() && rest.iter().all(|c| c.is_lowercase()) {
+            WordCase::TitleCase
+        } else {
+            WordCase::Unknown
+        }
+    }
+}
+
+/// Re-case `suggestion` so it matches the casing of `word`.
+pub fn match_case(word: &str, suggestion: &str) -> String {
+    match WordCase::of(word) {
+        WordCase::AllCaps => suggestion.to_uppercase(),
+        WordCase::AllLower => suggestion.to_lowercase(),
+        WordCase::TitleCase => capitalize(suggestion),
+        WordCase::Unknown => suggestion.to_string(),
+    }
+}
+
+/// Uppercase the first letter of a word, leaving the rest untouched.
+pub(crate) fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// A replacement of a whole identifier on a line.
+#[derive(Debug, PartialEq)]
+pub struct IdentifierEdit {
+    /// Start of the identifier in grapheme clusters
+    pub start_char: u32,
+    /// End of the identifier in grapheme clusters
+    pub end_char: u32,
+    pub new_text: String,
+}
+
+fn is_identifier_part(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Apply `suggestion` to the misspelled word between `start_char` and `end_char`
+/// (grapheme clusters) on `line`. The word may be one piece of a larger identifier,
+/// so the edit covers the whole identifier and keeps its casing style, e.g.
+/// `getUsrName` becomes `getUserName` and `MAX_BUFER_SIZE` becomes `MAX_BUFFER_SIZE`.
+pub fn apply_suggestion(
+    line: &str,
+    start_char: usize,
+    end_char: usize,
+    suggestion: &str,
+) -> IdentifierEdit {
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+    let end_char = end_char.min(graphemes.len());
+    let start_char = start_char.min(end_char);
+
+    let mut identifier_start = start_char;
+    while identifier_start > 0 && is_identifier_part(graphemes[identifier_start - 1]) {
+        identifier_start -= 1;
+    }
+    let mut identifier_end = end_char;
+    while identifier_end < graphemes.len() && is_identifier_part(graphemes[identifier_end]) {
+        identifier_end += 1;
+    }
+
+    let prefix = graphemes[identifier_start..start_char].concat();
+    let word = graphemes[start_char..end_char].concat();
+    let suffix = graphemes[end_char..identifier_end].concat();
+
+    let replacement = if prefix.is_empty() && suffix.is_empty() {
+        // A standalone word, multi-word suggestions can keep their spaces
+        match_case(&word, suggestion)
+    } else {
+        let identifier = format!("{prefix}{word}{suffix}");
+        join_identifier_parts(&identifier, &word, suggestion)
+    };
+
+    IdentifierEdit {
+        start_char: identifier_start as u32,
+        end_char: identifier_end as u32,
+        new_text: format!("{prefix}{replacement}{suffix}"),
+    }
+}
+
+/// Re-case `suggestion` for use inside `identifier`, joining multi-word
+/// suggestions with the identifier's separator style.
+fn join_identifier_parts(identifier: &str, word: &str, suggestion: &str) -> String {
+    let parts: Vec<&str> = suggestion
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|p| !p.is_empty())
+        .collect();
+    if parts.len() < 2 {
+        return match_case(word, suggestion);
+    }
+    let snake_case = identifier.contains('_');
+    let mut joined = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        if i == 0 {
+            joined.push_str(&match_case(word, part));
+        } else if snake_case {
+            joined.push('_');
+            joined.push_str(&match_case(word, part));
+        } else if WordCase::of(word) == WordCase::AllCaps {
+            joined.push_str(&part.to_uppercase());
+        } else {
+            joined.push_str(&capitalize(&part.to_lowercase()));
+        }
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_case() {
+        assert_eq!(WordCase::of("hello"), WordCase::AllLower);
+        assert_eq!(WordCase::of("HELLO"), WordCase::AllCaps);
+        assert_eq!(WordCase::of("Hello"), WordCase::TitleCase);
+        assert_eq!(WordCase::of("iPhone"), WordCase::Unknown);
+        assert_eq!(WordCase::of("don't"), WordCase::AllLower);
+        assert_eq!(WordCase::of("ÉCOLE"), WordCase::AllCaps);
+        assert_eq!(WordCase::of(""), WordCase::Unknown);
+    }
+
+    #[test]
+    fn test_match_case_unicode() {
+        assert_eq!(match_case("STRASE", "straße"), "STRASSE");
+        assert_eq!(match_case("ecol", "École"), "école");
+        assert_eq!(match_case("Ecol", "école"), "École");
+        assert_eq!(match_case("iPhne", "iPhone"), "iPhone");
+    }
+
+    fn apply(line: &str, word: &str, suggestion: &str) -> IdentifierEdit {
+        let start = line.find(word).unwrap();
+        let start_char = line[..start].graphemes(true).count();
+        let end_char = start_char + word.graphemes(true).count();
+        apply_suggestion(line, start_char, end_char, suggestion)
+    }
+
+    #[test]
+    fn test_apply_suggestion_camel_case() {
+        let edit = apply("let name = getUsrName();", "Usr", "user");
+        assert_eq!(
+            edit,
+            IdentifierEdit {
+                start_char: 11,
+                end_char: 21,
+                new_text: "getUserName".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_suggestion_screaming_snake_case() {
+        let edit = apply("const MAX_BUFER_SIZE: usize = 4;", "BUFER", "buffer");
+        assert_eq!(edit.new_text, "MAX_BUFFER_SIZE");
+        assert_eq!((edit.start_char, edit.end_char), (6, 20));
+    }
+
+    #[test]
+    fn test_apply_suggestion_first_part() {
+        let edit = apply("usrName = 1", "usr", "user");
+        assert_eq!(edit.new_text, "userName");
+        assert_eq!((edit.start_char, edit.end_char), (0, 7));
+    }
+
+    #[test]
+    fn test_apply_suggestion_multiple_words() {
+        assert_eq!(
+            apply("getUsrname()", "Usrname", "user name").new_text,
+            "getUserName"
+        );
+        assert_eq!(
+            apply("get_usrname()", "usrname", "user name").new_text,
+            "get_user_name"
+        );
+        assert_eq!(
+            apply("MAX_USRNAME", "USRNAME", "user name").new_text,
+            "MAX_USER_NAME"
+        );
+        // Standalone words keep the space
+        assert_eq!(apply("// alot of words", "alot", "a lot").new_text, "a lot");
+    }
+
+    #[test]
+    fn test_apply_suggestion_unicode() {
+        let edit = apply("größe_ÄNDRNG", "ÄNDRNG", "änderung");
+        assert_eq!(edit.new_text, "größe_ÄNDERUNG");
+        assert_eq!((edit.start_char, edit.end_char), (0, 12));
+    }
+}
@@ -0,0 +1,648 @@
+//! Expansion of Hunspell `.dic` stems into every word form their affix flags allow.
+//!
+//! Only the parts of the affix format needed to enumerate words are understood.
+//! Dictionaries using any other directive, like compounding, circumfixes, flag
+//! aliases or case rules, may accept words that aren't enumerated, in which case
+//! `Expansion::complete` is false and the word forms should only be used as a
+//! fast path in front of a real Hunspell check.
+
+use std::collections::{HashMap, HashSet};
+
+/// Directives that are accounted for when enumerating words, or that don't
+/// change which words a dictionary accepts. Any other directive could.
+const UNDERSTOOD_DIRECTIVES: &[&str] = &[
+    "SET",
+    "FLAG",
+    "PFX",
+    "SFX",
+    "NEEDAFFIX",
+    "PSEUDOROOT",
+    "ONLYINCOMPOUND",
+    // Only number compounds are accepted, see `expand`
+    "COMPOUNDRULE",
+    "COMPOUNDMIN",
+    // Applied by the compiled dictionary before looking words up
+    "ICONV",
+    // Suggestions, tokenizing and metadata
+    "TRY",
+    "KEY",
+    "REP",
+    "MAP",
+    "PHONE",
+    "OCONV",
+    "NOSUGGEST",
+    "WORDCHARS",
+    "MAXNGRAMSUGS",
+    "MAXCPDSUGS",
+    "MAXDIFF",
+    "ONLYMAXDIFF",
+    "NOSPLITSUGS",
+    "SUGSWITHDOTS",
+    "FORBIDWARN",
+    "WARN",
+    "NAME",
+    "VERSION",
+    "HOME",
+];
+
+#[derive(Debug, Default)]
+pub struct Expansion {
+    pub words: Vec<String>,
+    /// Whether `words` contains every word the dictionary accepts
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FlagType {
+    Char,
+    Long,
+    Numeric,
+}
+
+impl FlagType {
+    fn parse(&self, flags: &str) -> Vec<String> {
+        match self {
+            FlagType::Char => flags.chars().map(|c| c.to_string()).collect(),
+            FlagType::Long => flags
+                .chars()
+                .collect::<Vec<_>>()
+                .chunks(2)
+                .map(|c| c.iter().collect())
+                .collect(),
+            FlagType::Numeric => flags
+                .split(',')
+                .map(|f| f.trim().to_string())
+                .filter(|f| !f.is_empty())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ConditionPart {
+    Any,
+    Char(char),
+    Set { negated: bool, chars: Vec<char> },
+}
+
+impl ConditionPart {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            ConditionPart::Any => true,
+            ConditionPart::Char(expected) => *expected == c,
+            ConditionPart::Set { negated, chars } => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+fn parse_condition(condition: &str) -> Vec<ConditionPart> {
+    let mut parts = Vec::new();
+    let mut chars = condition.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => parts.push(ConditionPart::Any),
+            '[' => {
+                let mut set: Vec<char> = chars.by_ref().take_while(|&c| c != ']').collect();
+                let negated = set.first() == Some(&'^');
+                if negated {
+                    set.remove(0);
+                }
+                parts.push(ConditionPart::Set {
+                    negated,
+                    chars: set,
+                });
+            }
+            c => parts.push(ConditionPart::Char(c)),
+        }
+    }
+    // A lone "." means no condition
+    if parts == [ConditionPart::Any] {
+        parts.clear();
+    }
+    parts
+}
+
+#[derive(Debug, Clone)]
+struct AffixRule {
+    strip: String,
+    add: String,
+    continuation: Vec<String>,
+    condition: Vec<ConditionPart>,
+}
+
+#[derive(Debug, Clone)]
+struct AffixClass {
+    is_prefix: bool,
+    rules: Vec<AffixRule>,
+}
+
+impl AffixClass {
+    /// Apply every matching rule of this class to `word`.
+    fn apply(&self, word: &str) -> Vec<(String, &AffixRule)> {
+        let chars: Vec<char> = word.chars().collect();
+        let mut results = Vec::new();
+        for rule in &self.rules {
+            let condition_len = rule.condition.len();
+            let strip_len = rule.strip.chars().count();
+            if chars.len() < condition_len.max(strip_len) {
+                continue;
+            }
+            let (condition_chars, stripped) = if self.is_prefix {
+                (
+                    &chars[..condition_len],
+                    word.strip_prefix(rule.strip.as_str()),
+                )
+            } else {
+                (
+                    &chars[chars.len() - condition_len..],
+                    word.strip_suffix(rule.strip.as_str()),
+                )
+            };
+            let matches = rule
+                .condition
+                .iter()
+                .zip(condition_chars)
+                .all(|(part, &c)| part.matches(c));
+            let Some(stripped) = stripped else {
+                continue;
+            };
+            // Hunspell never strips a whole word
+            if !matches || (stripped.is_empty() && rule.add.is_empty()) {
+                continue;
+            }
+            let form = if self.is_prefix {
+                format!("{}{}", rule.add, stripped)
+            } else {
+                format!("{}{}", stripped, rule.add)
+            };
+            results.push((form, rule));
+        }
+        results
+    }
+}
+
+#[derive(Debug)]
+struct AffixFile {
+    flag_type: FlagType,
+    classes: HashMap<String, AffixClass>,
+    need_affix: Option<String>,
+    forbidden: Option<String>,
+    only_in_compound: Option<String>,
+    compound_rule_flags: HashSet<String>,
+    has_incomplete_directive: bool,
+}
+
+impl AffixFile {
+    fn parse(aff: &str) -> Self {
+        let mut flag_type = FlagType::Char;
+        // FLAG can appear anywhere before the affixes, find it first
+        for line in aff.lines() {
+            let mut fields = line.split_whitespace();
+            if fields.next() == Some("FLAG") {
+                flag_type = match fields.next() {
+                    Some("long") => FlagType::Long,
+                    Some("num") => FlagType::Numeric,
+                    _ => FlagType::Char,
+                };
+            }
+        }
+
+        let mut affix_file = AffixFile {
+            flag_type,
+            classes: HashMap::new(),
+            need_affix: None,
+            forbidden: None,
+            only_in_compound: None,
+            compound_rule_flags: HashSet::new(),
+            has_incomplete_directive: false,
+        };
+
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let Some(&directive) = fields.first() else {
+                continue;
+            };
+            match directive {
+                "PFX" | "SFX" => affix_file.parse_affix_line(&fields),
+                "NEEDAFFIX" | "PSEUDOROOT" => {
+                    affix_file.need_affix = fields.get(1).map(|f| f.to_string())
+                }
+                // Forbidden words are left out, but they also hide other casings
+                // of the word that spellbook would accept
+                "FORBIDDENWORD" => {
+                    affix_file.forbidden = fields.get(1).map(|f| f.to_string());
+                    affix_file.has_incomplete_directive = true
+                }
+                "ONLYINCOMPOUND" => {
+                    affix_file.only_in_compound = fields.get(1).map(|f| f.to_string())
+                }
+                // The first COMPOUNDRULE line is the number of rules
+                "COMPOUNDRULE" if fields.get(1).is_some_and(|f| f.parse::<usize>().is_err()) => {
+                    let rule = fields[1].replace(['*', '?', '(', ')'], "");
+                    affix_file
+                        .compound_rule_flags
+                        .extend(flag_type.parse(&rule));
+                }
+                d if d.chars().all(|c| c.is_ascii_uppercase())
+                    && !UNDERSTOOD_DIRECTIVES.contains(&d) =>
+                {
+                    affix_file.has_incomplete_directive = true
+                }
+                _ => {}
+            }
+        }
+        affix_file
+    }
+
+    fn parse_affix_line(&mut self, fields: &[&str]) {
+        let is_prefix = fields[0] == "PFX";
+        let Some(flag) = fields.get(1) else {
+            return;
+        };
+        // Header: PFX flag cross_product count. The cross product field is ignored
+        // since spellbook, which checks the words of incomplete dictionaries,
+        // combines any prefix and suffix.
+        if fields.len() == 4
+            && fields[3].parse::<usize>().is_ok()
+            && !self.classes.contains_key(*flag)
+        {
+            self.classes.insert(
+                flag.to_string(),
+                AffixClass {
+                    is_prefix,
+                    rules: Vec::new(),
+                },
+            );
+            return;
+        }
+        // Rule: PFX flag strip add[/flags] [condition [morphology...]]
+        let (Some(class), Some(strip), Some(add)) =
+            (self.classes.get_mut(*flag), fields.get(2), fields.get(3))
+        else {
+            return;
+        };
+        let (add, continuation) = match add.split_once('/') {
+            Some((add, flags)) => (add, self.flag_type.parse(flags)),
+            None => (*add, Vec::new()),
+        };
+        class.rules.push(AffixRule {
+            strip: if *strip == "0" {
+                String::new()
+            } else {
+                strip.to_string()
+            },
+            add: if add == "0" {
+                String::new()
+            } else {
+                add.to_string()
+            },
+            continuation,
+            condition: parse_condition(fields.get(4).unwrap_or(&".")),
+        });
+    }
+
+    fn has_flag(flags: &[String], flag: &Option<String>) -> bool {
+        flag.as_ref().is_some_and(|f| flags.contains(f))
+    }
+
+    /// Whether any of `flags` is an affix class matching `is_prefix`
+    fn has_affix(&self, flags: &[String], is_prefix: bool) -> bool {
+        flags.iter().any(|f| {
+            self.classes
+                .get(f)
+                .is_some_and(|c| c.is_prefix == is_prefix)
+        })
+    }
+
+    /// Whether any of `flags` changes how affixed forms are accepted in a way
+    /// that isn't enumerated, like flags of compounding.
+    fn has_unknown_flag(&self, flags: &[String]) -> bool {
+        flags
+            .iter()
+            .any(|f| !self.classes.contains_key(f) && self.need_affix.as_ref() != Some(f))
+    }
+
+    /// Add the suffixed forms of `word` given `flags` to `out`, with whether they
+    /// still need another affix. Clears `complete` if continuation flags had to
+    /// be skipped. Hunspell strips at most two suffixes, so only the suffixes of
+    /// the first suffix are followed.
+    fn expand_suffixes(
+        &self,
+        word: &str,
+        flags: &[String],
+        is_inner: bool,
+        out: &mut Vec<(String, bool)>,
+        complete: &mut bool,
+    ) {
+        for flag in flags {
+            let Some(class) = self.classes.get(flag).filter(|c| !c.is_prefix) else {
+                continue;
+            };
+            for (form, rule) in class.apply(word) {
+                out.push((
+                    form.clone(),
+                    Self::has_flag(&rule.continuation, &self.need_affix),
+                ));
+                // Prefixes on suffixed forms aren't enumerated
+                if self.has_affix(&rule.continuation, true)
+                    || self.has_unknown_flag(&rule.continuation)
+                {
+                    *complete = false;
+                }
+                if !is_inner && self.has_affix(&rule.continuation, false) {
+                    self.expand_suffixes(&form, &rule.continuation, true, out, complete);
+                }
+            }
+        }
+    }
+
+    fn expand_stem(
+        &self,
+        stem: &str,
+        flags: &[String],
+        out: &mut Vec<String>,
+        complete: &mut bool,
+    ) {
+        if Self::has_flag(flags, &self.forbidden) || Self::has_flag(flags, &self.only_in_compound) {
+            return;
+        }
+        if !Self::has_flag(flags, &self.need_affix) {
+            out.push(stem.to_string());
+        }
+        let mut suffixed = Vec::new();
+        self.expand_suffixes(stem, flags, false, &mut suffixed, complete);
+        out.extend(
+            suffixed
+                .iter()
+                .filter(|(_, needs_affix)| !needs_affix)
+                .map(|(form, _)| form.clone()),
+        );
+
+        // Prefixes apply to the stem and to its suffixed forms, which then have
+        // the affix they needed
+        for flag in flags {
+            let Some(class) = self.classes.get(flag).filter(|c| c.is_prefix) else {
+                continue;
+            };
+            for word in std::iter::once(stem).chain(suffixed.iter().map(|(form, _)| form.as_str()))
+            {
+                for (form, rule) in class.apply(word) {
+                    // Continuations of prefix rules aren't enumerated
+                    if rule.continuation.is_empty() {
+                        out.push(form);
+                    } else {
+                        *complete = false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Split a `.dic` line into its stem and flags the way spellbook does. Stems end
+/// at an unescaped slash, a tab or a space before a morphological field like
+/// `po:noun`; other spaces are part of the stem.
+fn parse_dic_line(line: &str) -> Option<(String, &str)> {
+    let mut stem = String::new();
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '/' if escaped => {
+                stem.pop();
+                stem.push('/');
+            }
+            '/' if i != 0 => {
+                let flags = line[i + 1..].split_whitespace().next().unwrap_or("");
+                return (!stem.is_empty()).then_some((stem, flags));
+            }
+            '\t' => break,
+            ' ' if starts_morphological_field(&line[i..]) => break,
+            c => stem.push(c),
+        }
+        escaped = c == '\\';
+    }
+    (!stem.is_empty()).then_some((stem, ""))
+}
+
+fn starts_morphological_field(text: &str) -> bool {
+    let mut chars = text.trim_start().chars();
+    chars.next().is_some_and(char::is_lowercase)
+        && chars.next().is_some_and(char::is_lowercase)
+        && chars.next() == Some(':')
+}
+
+/// Character replacements Hunspell applies to words before checking them (`ICONV`).
+pub fn input_conversions(aff: &str) -> Vec<(String, String)> {
+    aff.lines()
+        .filter_map(
+            |line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["ICONV", from, to] => Some((from.to_string(), to.to_string())),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+/// Enumerate every word form of a Hunspell dictionary.
+pub fn expand(aff: &str, dic: &str) -> Expansion {
+    let affix_file = AffixFile::parse(aff);
+    let mut complete = !affix_file.has_incomplete_directive;
+    let mut words = Vec::new();
+
+    // The first line is the approximate number of entries
+    for line in dic.lines().skip(1) {
+        // Some dictionaries use a leading slash for comments
+        let line = line.trim();
+        if line.starts_with('/') && line.len() > 1 {
+            continue;
+        }
+        let Some((stem, flags)) = parse_dic_line(line) else {
+            continue;
+        };
+        let flags = affix_file.flag_type.parse(flags);
+        // Compound rules can build words that aren't listed. Codebook never checks
+        // words with digits in them, so number compounds (like "21st") don't matter.
+        if complete
+            && flags
+                .iter()
+                .any(|f| affix_file.compound_rule_flags.contains(f))
+            && !stem.chars().any(|c| c.is_ascii_digit())
+        {
+            complete = false;
+        }
+        affix_file.expand_stem(&stem, &flags, &mut words, &mut complete);
+    }
+
+    words.sort_unstable();
+    words.dedup();
+    Expansion { words, complete }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8
+PFX A Y 1
+PFX A   0     re         .
+
+SFX D Y 4
+SFX D   0     d          e
+SFX D   y     ied        [^aeiou]y
+SFX D   0     ed         [^ey]
+SFX D   0     ed         [aeiou]y
+
+SFX S Y 1
+SFX S   0     s          .
+
+NEEDAFFIX n
+";
+
+    const DIC: &str = "4
+create/AD
+try/DS
+work/S
+bound/n
+";
+
+    #[test]
+    fn test_expand() {
+        let expansion = expand(AFF, DIC);
+        let expected = [
+            "create",
+            "created",
+            "recreate",
+            "recreated",
+            "tried",
+            "trys",
+            "try",
+            "work",
+            "works",
+        ];
+        let mut expected: Vec<String> = expected.iter().map(|s| s.to_string()).collect();
+        expected.sort();
+        assert_eq!(expansion.words, expected);
+        assert!(expansion.complete);
+    }
+
+    #[test]
+    fn test_forbidden_words_are_left_out() {
+        let aff = format!("{AFF}FORBIDDENWORD !\n");
+        let expansion = expand(&aff, "2\nwork/S\ncolour/!\n");
+        assert_eq!(expansion.words, vec!["work", "works"]);
+        assert!(!expansion.complete);
+    }
+
+    #[test]
+    fn test_compound_flag_is_incomplete() {
+        let aff = format!("{AFF}COMPOUNDFLAG X\n");
+        assert!(!expand(&aff, DIC).complete);
+    }
+
+    #[test]
+    fn test_numeric_compound_rules_are_complete() {
+        let aff = format!("{AFF}COMPOUNDRULE 1\nCOMPOUNDRULE n*1t\n");
+        let dic = "2\n0/nm\n1st/p1t\n";
+        assert!(expand(&aff, dic).complete);
+        let dic = "2\n0/nm\nfoo/n\n";
+        assert!(!expand(&aff, dic).complete);
+    }
+
+    #[test]
+    fn test_skipped_continuations_are_incomplete() {
+        // A suffix continued by another suffix is enumerated
+        let aff = "SFX A Y 1\nSFX A 0 ing/B .\nSFX B Y 1\nSFX B 0 s .\nPFX P Y 1\nPFX P 0 un .\n";
+        let expansion = expand(aff, "1\nwork/A\n");
+        assert_eq!(expansion.words, vec!["work", "working", "workings"]);
+        assert!(expansion.complete);
+        // Prefixes on suffixed forms aren't
+        let aff = aff.replace("ing/B", "ing/P");
+        assert!(!expand(&aff, "1\nwork/A\n").complete);
+        // Nor continuations of prefixes
+        let aff = "PFX P Y 1\nPFX P 0 un/S .\nSFX S Y 1\nSFX S 0 s .\n";
+        assert!(!expand(aff, "1\ndo/P\n").complete);
+        // Hunspell strips at most two suffixes, so deeper ones never match
+        let aff = "SFX A Y 1\nSFX A 0 a/B .\nSFX B Y 1\nSFX B 0 b/C .\nSFX C Y 1\nSFX C 0 c/D .\nSFX D Y 1\nSFX D 0 d .\n";
+        let expansion = expand(aff, "1\nx/A\n");
+        assert_eq!(expansion.words, vec!["x", "xa", "xab"]);
+        assert!(expansion.complete);
+    }
+
+    #[test]
+    fn test_unknown_directives_are_incomplete() {
+        let dic = "1\nword\n";
+        assert!(expand("TRY abc\nREP 0\n", dic).complete);
+        assert!(!expand("KEEPCASE K\n", dic).complete);
+        assert!(!expand("FORBIDDENWORD X\n", dic).complete);
+        assert!(!expand("CHECKSHARPS\n", dic).complete);
+    }
+
+    #[test]
+    fn test_prefixes_combine_with_any_suffix() {
+        let aff = "PFX P N 1\nPFX P 0 un .\nSFX S N 1\nSFX S 0 s .\n";
+        assert_eq!(
+            expand(aff, "1\ndo/PS\n").words,
+            vec!["do", "dos", "undo", "undos"]
+        );
+    }
+
+    #[test]
+    fn test_need_affix_on_suffix() {
+        // A suffix that needs another affix only appears with it
+        let aff = "NEEDAFFIX N\nSFX A Y 1\nSFX A 0 er/BN .\nSFX B Y 1\nSFX B 0 s .\nPFX P Y 1\nPFX P 0 re .\n";
+        assert_eq!(
+            expand(aff, "1\nmak/AP\n").words,
+            vec!["mak", "makers", "remak", "remaker", "remakers"]
+        );
+    }
+
+    #[test]
+    fn test_long_flags() {
+        let aff = "FLAG long\nSFX Aa Y 1\nSFX Aa 0 s .\n";
+        let dic = "1\ncat/Aa\n";
+        assert_eq!(expand(aff, dic).words, vec!["cat", "cats"]);
+    }
+
+    #[test]
+    fn test_condition() {
+        let parts = parse_condition("[^aeiou]y");
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0].matches('r'));
+        assert!(!parts[0].matches('a'));
+        assert!(parts[1].matches('y'));
+        assert!(parse_condition(".").is_empty());
+    }
+
+    #[test]
+    fn test_input_conversions() {
+        let aff = "ICONV 1\nICONV ’ '\n";
+        assert_eq!(
+            input_conversions(aff),
+            vec![("’".to_string(), "'".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_dic_line() {
+        assert_eq!(
+            parse_dic_line("and\\/or/S"),
+            Some(("and/or".to_string(), "S"))
+        );
+        assert_eq!(
+            parse_dic_line("word\tpo:noun"),
+            Some(("word".to_string(), ""))
+        );
+        assert_eq!(
+            parse_dic_line("word/S po:noun"),
+            Some(("word".to_string(), "S"))
+        );
+        assert_eq!(
+            parse_dic_line("word  po:noun"),
+            Some(("word".to_string(), ""))
+        );
+        assert_eq!(
+            parse_dic_line("a lot ph:alot"),
+            Some(("a lot".to_string(), ""))
+        );
+    }
+}
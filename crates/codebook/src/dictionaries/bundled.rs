@@ -0,0 +1,46 @@
+//! Dictionaries embedded in the binary with the `bundled-dictionaries` feature.
+//! They are used whenever a dictionary can't be downloaded, so spell checking
+//! keeps working offline.
+
+#[derive(Debug)]
+pub struct BundledDictionary {
+    pub id: &'static str,
+    /// Contents of the `.aff` and `.dic` files
+    pub hunspell: Option<(&'static str, &'static str)>,
+    pub word_list: Option<&'static str>,
+}
+
+include!(concat!(env!("OUT_DIR"), "/bundled.gen.rs"));
+
+pub fn get_bundled_dictionary(id: &str) -> Option<&'static BundledDictionary> {
+    BUNDLED_DICTIONARIES.iter().find(|d| d.id == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "bundled-dictionaries")]
+    fn test_en_us_is_bundled() {
+        let en_us = get_bundled_dictionary("en_us").unwrap();
+        let (aff, dic) = en_us.hunspell.unwrap();
+        assert!(aff.starts_with("SET UTF-8"));
+        assert!(!dic.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-dictionaries")]
+    fn test_default_word_lists_are_bundled() {
+        for id in ["software_terms", "computing_acronyms"] {
+            let word_list = get_bundled_dictionary(id).unwrap().word_list.unwrap();
+            assert!(!word_list.is_empty(), "{id} has no words");
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "bundled-dictionaries"))]
+    fn test_nothing_is_bundled() {
+        assert!(get_bundled_dictionary("en_us").is_none());
+    }
+}
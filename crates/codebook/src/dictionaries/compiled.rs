@@ -0,0 +1,538 @@
+//! Precompiled dictionary format that can be memory-mapped instead of parsed.
+//!
+//! A compiled file stores every word form of a dictionary in an FST, so checking
+//! a word is a lookup in the mapped file. Hunspell dictionaries also keep their
+//! original `.aff`/`.dic` text, which is only parsed when a suggestion is needed
+//! or when the affix rules can't be fully expanded into the word set.
+//!
+//! Layout (integers are little endian):
+//!
+//! | field            | size            |
+//! |------------------|-----------------|
+//! | magic `CBOOKDIC` | 8               |
+//! | format version   | 4               |
+//! | flags            | 4               |
+//! | checksum length  | 4               |
+//! | words length     | 8               |
+//! | aff length       | 8               |
+//! | dic length       | 8               |
+//! | source checksum  | checksum length |
+//! | words (FST set)  | words length    |
+//! | aff text         | aff length      |
+//! | dic text         | dic length      |
+
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    error::Error,
+    fs::File,
+    ops::Range,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use log::{debug, error};
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
+
+use super::{
+    affix,
+    dictionary::{Dictionary, HunspellDictionary},
+};
+use crate::casing::{WordCase, capitalize};
+
+/// File extension of compiled dictionaries.
+pub const EXTENSION: &str = "cbd";
+
+const MAGIC: &[u8; 8] = b"CBOOKDIC";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 44;
+
+const FLAG_HUNSPELL: u32 = 1;
+const FLAG_COMPLETE: u32 = 1 << 1;
+
+/// Checksum identifying the sources a dictionary was compiled from.
+pub fn source_checksum(sources: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for source in sources {
+        hasher.update((source.len() as u64).to_le_bytes());
+        hasher.update(source);
+    }
+    format!("sha256:{:x}", hasher.finalize())
+}
+
+/// Compile a plain word list, one word per line. Matching is ASCII case-insensitive,
+/// like `TextDictionary`.
+pub fn compile_word_list(word_list: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let words: BTreeSet<String> = word_list
+        .lines()
+        .filter(|s| !s.is_empty() && !s.starts_with('#'))
+        .map(|s| s.to_ascii_lowercase())
+        .collect();
+    let checksum = source_checksum(&[word_list.as_bytes()]);
+    encode(FLAG_COMPLETE, &checksum, &words, "", "")
+}
+
+/// Compile a Hunspell dictionary from the contents of its `.aff` and `.dic` files.
+pub fn compile_hunspell(aff: &str, dic: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let expansion = affix::expand(aff, dic);
+    let mut words: BTreeSet<String> = BTreeSet::new();
+    for word in expansion.words {
+        // Hunspell accepts mixed case words like "McDonald" in all caps
+        if WordCase::of(&word) == WordCase::Unknown {
+            words.insert(word.to_uppercase());
+        }
+        words.insert(word);
+    }
+    let mut flags = FLAG_HUNSPELL;
+    if expansion.complete {
+        flags |= FLAG_COMPLETE;
+    }
+    let checksum = source_checksum(&[aff.as_bytes(), dic.as_bytes()]);
+    encode(flags, &checksum, &words, aff, dic)
+}
+
+fn encode(
+    flags: u32,
+    checksum: &str,
+    words: &BTreeSet<String>,
+    aff: &str,
+    dic: &str,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let words = fst::Set::from_iter(words)?.into_fst().into_inner();
+    let mut bytes =
+        Vec::with_capacity(HEADER_LEN + checksum.len() + words.len() + aff.len() + dic.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&flags.to_le_bytes());
+    bytes.extend_from_slice(&(checksum.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&(words.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&(aff.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&(dic.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(checksum.as_bytes());
+    bytes.extend_from_slice(&words);
+    bytes.extend_from_slice(aff.as_bytes());
+    bytes.extend_from_slice(dic.as_bytes());
+    Ok(bytes)
+}
+
+/// Write a compiled dictionary so readers never see a partially written file.
+pub fn write(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension(format!("{EXTENSION}.{}.tmp", std::process::id()));
+    std::fs::write(&tmp_path, bytes)?;
+    std::fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp_path);
+    })
+}
+
+/// A section of the mapped file.
+#[derive(Clone)]
+struct MappedBytes {
+    mmap: Arc<Mmap>,
+    range: Range<usize>,
+}
+
+impl AsRef<[u8]> for MappedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.mmap[self.range.clone()]
+    }
+}
+
+pub struct CompiledDictionary {
+    words: fst::Set<MappedBytes>,
+    source_checksum: String,
+    hunspell: bool,
+    complete: bool,
+    aff: MappedBytes,
+    dic: MappedBytes,
+    input_conversions: Vec<(String, String)>,
+    /// Parsed on first use, for suggestions and words the FST can't answer
+    fallback: OnceLock<Option<HunspellDictionary>>,
+}
+
+impl std::fmt::Debug for CompiledDictionary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompiledDictionary")
+            .field("words", &self.words.len())
+            .field("source_checksum", &self.source_checksum)
+            .field("hunspell", &self.hunspell)
+            .field("complete", &self.complete)
+            .finish()
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> usize {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap()) as usize
+}
+
+impl CompiledDictionary {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        // SAFETY: compiled files are only ever replaced atomically with `write`,
+        // never modified in place, so the mapping can't change under us.
+        let mmap = Arc::new(unsafe { Mmap::map(&file)? });
+        Self::from_mmap(mmap)
+    }
+
+    fn from_mmap(mmap: Arc<Mmap>) -> Result<Self, Box<dyn Error>> {
+        let bytes: &[u8] = &mmap;
+        if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+            return Err("Not a compiled dictionary".into());
+        }
+        let version = read_u32(bytes, 8);
+        if version != VERSION {
+            return Err(format!("Unsupported compiled dictionary version: {}", version).into());
+        }
+        let flags = read_u32(bytes, 12);
+        let checksum_len = read_u32(bytes, 16) as usize;
+        let words_len = read_u64(bytes, 20);
+        let aff_len = read_u64(bytes, 28);
+        let dic_len = read_u64(bytes, 36);
+
+        let checksum_end = HEADER_LEN + checksum_len;
+        let words_end = checksum_end.saturating_add(words_len);
+        let aff_end = words_end.saturating_add(aff_len);
+        let dic_end = aff_end.saturating_add(dic_len);
+        if dic_end != bytes.len() {
+            return Err("Compiled dictionary is truncated".into());
+        }
+        let source_checksum = std::str::from_utf8(&bytes[HEADER_LEN..checksum_end])?.to_string();
+        let aff_text = std::str::from_utf8(&bytes[words_end..aff_end])?;
+        std::str::from_utf8(&bytes[aff_end..dic_end])?;
+        let input_conversions = affix::input_conversions(aff_text);
+
+        let section = |range: Range<usize>| MappedBytes {
+            mmap: mmap.clone(),
+            range,
+        };
+        let words = fst::Set::new(section(checksum_end..words_end))?;
+        Ok(Self {
+            words,
+            source_checksum,
+            hunspell: flags & FLAG_HUNSPELL != 0,
+            complete: flags & FLAG_COMPLETE != 0,
+            aff: section(words_end..aff_end),
+            dic: section(aff_end..dic_end),
+            input_conversions,
+            fallback: OnceLock::new(),
+        })
+    }
+
+    /// Checksum of the sources this dictionary was compiled from.
+    pub fn source_checksum(&self) -> &str {
+        &self.source_checksum
+    }
+
+    fn section_text(section: &MappedBytes) -> &str {
+        // Validated when the file was opened
+        std::str::from_utf8(section.as_ref()).unwrap()
+    }
+
+    fn fallback(&self) -> Option<&HunspellDictionary> {
+        self.fallback
+            .get_or_init(|| {
+                debug!("Parsing Hunspell sources of compiled dictionary");
+                HunspellDictionary::parse(
+                    Self::section_text(&self.aff),
+                    Self::section_text(&self.dic),
+                )
+                .inspect_err(|e| error!("Failed to parse compiled dictionary sources: {}", e))
+                .ok()
+            })
+            .as_ref()
+    }
+
+    /// Apply the `ICONV` replacements, preferring the longest match at each
+    /// position like Hunspell.
+    fn convert_input<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        if !self
+            .input_conversions
+            .iter()
+            .any(|(from, _)| word.contains(from.as_str()))
+        {
+            return Cow::Borrowed(word);
+        }
+        let mut converted = String::with_capacity(word.len());
+        let mut rest = word;
+        while let Some(c) = rest.chars().next() {
+            match self
+                .input_conversions
+                .iter()
+                .filter(|(from, _)| rest.starts_with(from.as_str()))
+                .max_by_key(|(from, _)| from.len())
+            {
+                Some((from, to)) => {
+                    converted.push_str(to);
+                    rest = &rest[from.len()..];
+                }
+                None => {
+                    converted.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        Cow::Owned(converted)
+    }
+
+    /// Look up `word` the way Hunspell matches case: lowercase words may be
+    /// written title or upper case, and vice versa.
+    fn contains_any_case(&self, word: &str) -> bool {
+        if self.words.contains(word) {
+            return true;
+        }
+        match WordCase::of(word) {
+            WordCase::AllLower => {
+                self.words.contains(capitalize(word)) || self.contains_upper(&word.to_uppercase())
+            }
+            WordCase::TitleCase => self.words.contains(word.to_lowercase()),
+            WordCase::AllCaps => self.contains_upper(word),
+            // Mixed case like "BlackBerrying" or "ABs"
+            WordCase::Unknown => {
+                self.words.contains(title_case(word)) || self.contains_upper(&word.to_uppercase())
+            }
+        }
+    }
+
+    /// Look up an upper case word as any casing of it.
+    fn contains_upper(&self, word: &str) -> bool {
+        if self.words.contains(word)
+            || self.words.contains(title_case(word))
+            || self.words.contains(word.to_lowercase())
+        {
+            return true;
+        }
+        // Words with an elided prefix, like "SANT'ELIA" for "Sant'Elia"
+        let Some(apostrophe) = word.find('\'').filter(|&i| i != word.len() - 1) else {
+            return false;
+        };
+        let (prefix, rest) = word.split_at(apostrophe + 1);
+        let rest = title_case(rest);
+        self.words.contains(prefix.to_lowercase() + &rest)
+            || self.words.contains(title_case(prefix) + &rest)
+    }
+}
+
+fn title_case(word: &str) -> String {
+    capitalize(&word.to_lowercase())
+}
+
+impl Dictionary for CompiledDictionary {
+    fn check(&self, word: &str) -> bool {
+        if !self.hunspell {
+            return self.words.contains(word.to_ascii_lowercase());
+        }
+        let word = self.convert_input(word);
+        if self.contains_any_case(&word) {
+            return true;
+        }
+        !self.complete && self.fallback().is_some_and(|d| d.check(&word))
+    }
+
+    fn suggest(&self, word: &str) -> Vec<String> {
+        if !self.hunspell {
+            return vec![];
+        }
+        self.fallback().map(|d| d.suggest(word)).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEM_SAMPLE_STRIDE: usize = 500;
+
+    fn load(bytes: &[u8]) -> CompiledDictionary {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.cbd");
+        write(&path, bytes).unwrap();
+        CompiledDictionary::open(&path).unwrap()
+    }
+
+    fn en_index() -> (String, String) {
+        (
+            std::fs::read_to_string("./tests/en_index.aff").unwrap(),
+            std::fs::read_to_string("./tests/en_index.dic").unwrap(),
+        )
+    }
+
+    /// Words to check both dictionaries with: every word form, the stems with
+    /// each affix rule applied whether it should match or not, and their
+    /// casings.
+    fn candidates(aff: &str, dic: &str) -> Vec<String> {
+        let mut prefixes = Vec::new();
+        let mut suffixes = Vec::new();
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [kind, _, strip, add, ..] = fields[..] else {
+                continue;
+            };
+            // Skip class headers, whose fourth field is the number of rules
+            if add.parse::<usize>().is_ok() {
+                continue;
+            }
+            let strip = strip.trim_start_matches('0');
+            let add = add.split('/').next().unwrap().trim_start_matches('0');
+            match kind {
+                "PFX" => prefixes.push((strip, add)),
+                "SFX" => suffixes.push((strip, add)),
+                _ => {}
+            }
+        }
+
+        let mut words = affix::expand(aff, dic).words;
+        for line in dic.lines().skip(1) {
+            let stem = line.split(['/', '\t']).next().unwrap();
+            let mut forms = vec![stem.to_string()];
+            for (strip, add) in &suffixes {
+                if let Some(base) = stem.strip_suffix(strip) {
+                    forms.push(format!("{base}{add}"));
+                }
+            }
+            for form in forms.clone() {
+                for (strip, add) in &prefixes {
+                    if let Some(base) = form.strip_prefix(strip) {
+                        forms.push(format!("{add}{base}"));
+                    }
+                }
+            }
+            words.extend(forms);
+        }
+        let casings: Vec<String> = words
+            .iter()
+            .flat_map(|w| [w.to_lowercase(), w.to_uppercase(), capitalize(w)])
+            .collect();
+        words.extend(casings);
+        words.retain(|w| !w.is_empty() && w.chars().all(|c| c.is_alphabetic() || c == '\''));
+        words.sort_unstable();
+        words.dedup();
+        words
+    }
+
+    /// Check that the compiled dictionary agrees with Hunspell on every candidate
+    /// word without falling back to it.
+    fn assert_matches_hunspell(aff: &str, dic: &str) {
+        let compiled = load(&compile_hunspell(aff, dic).unwrap());
+        let hunspell = HunspellDictionary::parse(aff, dic).unwrap();
+        assert!(compiled.complete);
+        let mismatches: Vec<String> = candidates(aff, dic)
+            .into_iter()
+            .filter(|word| compiled.check(word) != hunspell.check(word))
+            .collect();
+        assert!(mismatches.is_empty(), "{mismatches:?}");
+        assert!(compiled.fallback.get().is_none());
+    }
+
+    #[test]
+    fn test_compiled_en_us_matches_hunspell() {
+        let aff = std::fs::read_to_string("../../dictionaries/en_us/dict/en_US-large.aff").unwrap();
+        let dic = std::fs::read_to_string("../../dictionaries/en_us/dict/en_US-large.dic").unwrap();
+        // A sample of the stems keeps the test fast
+        let sample: String = dic
+            .lines()
+            .step_by(STEM_SAMPLE_STRIDE)
+            .map(|l| format!("{l}\n"))
+            .collect();
+        assert_matches_hunspell(&aff, &sample);
+    }
+
+    #[test]
+    fn test_compiled_de_sample_matches_hunspell() {
+        // A small made-up German dictionary, for non-ASCII letters, capitalized
+        // nouns, suffix continuations, NEEDAFFIX and ICONV
+        let aff = std::fs::read_to_string("./tests/de_sample.aff").unwrap();
+        let dic = std::fs::read_to_string("./tests/de_sample.dic").unwrap();
+        assert_matches_hunspell(&aff, &dic);
+        let compiled = load(&compile_hunspell(&aff, &dic).unwrap());
+        for word in [
+            "Straßen",
+            "STRASSEN",
+            "unglückliche",
+            "Mädchens",
+            "D'ARTAGNAN",
+        ] {
+            assert_eq!(compiled.check(word), word != "STRASSEN", "{word}");
+        }
+    }
+
+    #[test]
+    fn test_input_conversion_prefers_longest_match() {
+        let aff = "ICONV 2\nICONV a b\nICONV aa c\n";
+        let dic = "1\ncb\n";
+        let compiled = load(&compile_hunspell(aff, dic).unwrap());
+        let hunspell = HunspellDictionary::parse(aff, dic).unwrap();
+        assert!(compiled.check("aaa"));
+        assert!(hunspell.check("aaa"));
+    }
+
+    #[test]
+    fn test_compiled_hunspell_matches_hunspell() {
+        let (aff, dic) = en_index();
+        let compiled = load(&compile_hunspell(&aff, &dic).unwrap());
+        let hunspell = HunspellDictionary::parse(&aff, &dic).unwrap();
+        assert!(compiled.complete);
+        for word in [
+            "world",
+            "worlds",
+            "World",
+            "WORLD",
+            "alice",
+            "Alice",
+            "created",
+            "recreated",
+            "doesn’t",
+            "wrld",
+            "helo",
+            "thier",
+        ] {
+            assert_eq!(compiled.check(word), hunspell.check(word), "{word}");
+        }
+        // Checks are answered without parsing the sources
+        assert!(compiled.fallback.get().is_none());
+        assert!(compiled.suggest("wrld").contains(&"world".to_string()));
+    }
+
+    #[test]
+    fn test_compiled_word_list() {
+        let dictionary = load(&compile_word_list("# comment\nfoo\nBar\n").unwrap());
+        assert!(dictionary.check("foo"));
+        assert!(dictionary.check("FOO"));
+        assert!(dictionary.check("bar"));
+        assert!(!dictionary.check("comment"));
+        assert!(dictionary.suggest("fo").is_empty());
+    }
+
+    #[test]
+    fn test_source_checksum() {
+        let (aff, dic) = en_index();
+        let dictionary = load(&compile_hunspell(&aff, &dic).unwrap());
+        assert_eq!(
+            dictionary.source_checksum(),
+            source_checksum(&[aff.as_bytes(), dic.as_bytes()])
+        );
+        assert_ne!(
+            source_checksum(&[b"ab", b"c"]),
+            source_checksum(&[b"a", b"bc"])
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.cbd");
+        std::fs::write(&path, b"not a dictionary").unwrap();
+        assert!(CompiledDictionary::open(&path).is_err());
+
+        let mut bytes = compile_word_list("foo\n").unwrap();
+        bytes.pop();
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(CompiledDictionary::open(&path).is_err());
+    }
+}
@@ -7,22 +7,19 @@ use std::{
     sync::{Arc, RwLock},
 };
 
+use crate::casing::match_case;
 use crate::parser::{WordLocation, find_locations};
 use crate::queries::LanguageType;
 use regex::Regex;
 
+/// Upper bound of suggestions a single dictionary offers before ranking.
+const MAX_SUGGESTIONS: usize = 10;
+
 pub trait Dictionary: Send + Sync {
     fn check(&self, word: &str) -> bool;
     fn suggest(&self, word: &str) -> Vec<String>;
 }
 
-enum WordCase {
-    AllCaps,
-    AllLower,
-    TitleCase,
-    Unknown,
-}
-
 #[derive(Debug)]
 pub struct HunspellDictionary {
     dictionary: spellbook::Dictionary,
@@ -34,7 +31,12 @@ impl HunspellDictionary {
     pub fn new(aff_path: &str, dic_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let aff = std::fs::read_to_string(aff_path)?;
         let dic = std::fs::read_to_string(dic_path)?;
-        let dict = spellbook::Dictionary::new(&aff, &dic)
+        Self::parse(&aff, &dic)
+    }
+
+    /// Build a dictionary from the contents of its `.aff` and `.dic` files.
+    pub fn parse(aff: &str, dic: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let dict = spellbook::Dictionary::new(aff, dic)
             .map_err(|e| format!("Dictionary parse error: {}", e))?;
 
         Ok(HunspellDictionary {
@@ -47,18 +49,6 @@ impl HunspellDictionary {
             ))),
         })
     }
-    fn get_word_case(&self, word: &str) -> WordCase {
-        if word.chars().all(char::is_uppercase) {
-            return WordCase::AllCaps;
-        }
-        if word.chars().all(char::is_lowercase) {
-            return WordCase::AllLower;
-        }
-        if word.chars().next().unwrap().is_uppercase() {
-            return WordCase::TitleCase;
-        }
-        WordCase::Unknown
-    }
 }
 
 impl Dictionary for HunspellDictionary {
@@ -99,26 +89,11 @@ impl Dictionary for HunspellDictionary {
         // If not in cache, generate suggestions
         let mut suggestions = Vec::new();
         self.dictionary.suggest(word, &mut suggestions);
-        suggestions.truncate(5);
+        suggestions.truncate(MAX_SUGGESTIONS);
 
-        if !suggestions.is_empty() {
-            let word_case = self.get_word_case(word);
-
-            // Apply case transformations
-            for suggestion in &mut suggestions {
-                match word_case {
-                    WordCase::AllCaps => {
-                        suggestion.make_ascii_uppercase();
-                    }
-                    WordCase::AllLower => {
-                        suggestion.make_ascii_lowercase();
-                    }
-                    WordCase::TitleCase => {
-                        // Leave it alone if it's a title case
-                    }
-                    WordCase::Unknown => {}
-                }
-            }
+        // Match the casing of the misspelled word
+        for suggestion in &mut suggestions {
+            *suggestion = match_case(word, suggestion);
         }
 
         // Cache the result if non-empty
@@ -1,27 +1,38 @@
 use std::{
     collections::HashMap,
-    path::PathBuf,
+    error::Error,
+    path::{Path, PathBuf},
     sync::{Arc, RwLock},
 };
 
 use super::{
+    bundled::get_bundled_dictionary,
+    compiled::{self, CompiledDictionary},
     dictionary::{self, TextDictionary},
-    repo::{DictionaryRepo, HunspellRepo, TextRepo, get_repo},
+    repo::{DictionaryRepo, HunspellRepo, TextRepo, get_repo, is_immutable_url},
 };
-use codebook_downloader::Downloader;
+use codebook_downloader::{Downloader, DownloaderOptions};
 use dictionary::{Dictionary, HunspellDictionary};
-use log::{debug, error};
+use log::{debug, error, info};
+
+/// Directory under the cache dir holding compiled dictionaries.
+const COMPILED_DIR: &str = "compiled";
 
 pub struct DictionaryManager {
     dictionary_cache: Arc<RwLock<HashMap<String, Arc<dyn Dictionary>>>>,
-    downloader: Downloader,
+    /// None if the cache dir can't be used, then only bundled dictionaries are available
+    downloader: Option<Downloader>,
+    compiled_dir: PathBuf,
 }
 
 impl DictionaryManager {
-    pub fn new(cache_dir: &PathBuf) -> Self {
+    pub fn new(cache_dir: &PathBuf, downloader_options: DownloaderOptions) -> Self {
         Self {
             dictionary_cache: Arc::new(RwLock::new(HashMap::new())),
-            downloader: Downloader::new(cache_dir).unwrap(),
+            downloader: Downloader::with_options(cache_dir, downloader_options)
+                .inspect_err(|e| error!("Failed to create dictionary downloader: {:?}", e))
+                .ok(),
+            compiled_dir: cache_dir.join(COMPILED_DIR),
         }
     }
 
@@ -55,29 +66,75 @@ impl DictionaryManager {
         }
     }
 
+    /// Download a file and read it, or None if that isn't possible. Files of
+    /// URLs pinned to a commit are rejected if they don't have the expected
+    /// hash. Others can change, they are only checked for truncation when
+    /// downloaded and fetched again if they can't be read or parsed.
+    fn download(&self, url: &str, expected_sha256: Option<&str>) -> Option<String> {
+        let downloader = self.downloader.as_ref()?;
+        let expected_sha256 = expected_sha256.filter(|_| is_immutable_url(url));
+        for attempt in 0..2 {
+            let path = match expected_sha256 {
+                Some(hash) => downloader.get_verified(url, hash),
+                None => downloader.get(url),
+            };
+            let path = match path {
+                Ok(path) => path,
+                Err(e) => {
+                    error!("Error: {:?}", e);
+                    return None;
+                }
+            };
+            match std::fs::read_to_string(&path) {
+                Ok(text) => return Some(text),
+                Err(e) => {
+                    error!("Error: {:?}", e);
+                    if attempt == 0 {
+                        self.invalidate(url);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Drop a downloaded file whose content turned out to be unusable.
+    fn invalidate(&self, url: &str) {
+        if let Some(downloader) = &self.downloader
+            && let Err(e) = downloader.invalidate(url)
+        {
+            error!("Failed to remove {} from the cache: {:?}", url, e);
+        }
+    }
+
     fn get_hunspell_dictionary(&self, repo: HunspellRepo) -> Option<Arc<dyn Dictionary>> {
-        let aff_path = match self.downloader.get(&repo.aff_url) {
-            Ok(path) => path,
-            Err(e) => {
-                error!("Error: {:?}", e);
-                return None;
+        let (aff, dic) = match (
+            self.download(&repo.aff_url, repo.aff_sha256.as_deref()),
+            self.download(&repo.dict_url, repo.dict_sha256.as_deref()),
+        ) {
+            (Some(aff), Some(dic)) => (aff, dic),
+            _ => {
+                let (aff, dic) = get_bundled_dictionary(&repo.name)?.hunspell?;
+                info!("Using bundled dictionary: {}", repo.name);
+                (aff.to_string(), dic.to_string())
             }
         };
-        let dic_path = match self.downloader.get(&repo.dict_url) {
-            Ok(path) => path,
+        let checksum = compiled::source_checksum(&[aff.as_bytes(), dic.as_bytes()]);
+        if let Some(dict) = self.get_compiled_dictionary(&repo.name, &checksum, || {
+            compiled::compile_hunspell(&aff, &dic)
+        }) {
+            return Some(Arc::new(dict));
+        }
+        let dict = match HunspellDictionary::parse(&aff, &dic) {
+            Ok(dict) => dict,
             Err(e) => {
                 error!("Error: {:?}", e);
+                // Fetch fresh copies next time
+                self.invalidate(&repo.aff_url);
+                self.invalidate(&repo.dict_url);
                 return None;
             }
         };
-        let dict =
-            match HunspellDictionary::new(aff_path.to_str().unwrap(), dic_path.to_str().unwrap()) {
-                Ok(dict) => dict,
-                Err(e) => {
-                    error!("Error: {:?}", e);
-                    return None;
-                }
-            };
         Some(Arc::new(dict))
     }
 
@@ -85,14 +142,80 @@ impl DictionaryManager {
         if repo.text.is_some() {
             return Some(Arc::new(TextDictionary::new(repo.text.unwrap())));
         }
-        let text_path = match self.downloader.get(&repo.url.unwrap()) {
-            Ok(path) => path,
-            Err(e) => {
-                error!("Error: {:?}", e);
-                return None;
+        let word_list = match self.download(&repo.url.unwrap(), repo.sha256.as_deref()) {
+            Some(word_list) => word_list,
+            None => {
+                let word_list = get_bundled_dictionary(&repo.name)?.word_list?;
+                info!("Using bundled dictionary: {}", repo.name);
+                word_list.to_string()
             }
         };
-        let dict = TextDictionary::new_from_path(&text_path);
-        Some(Arc::new(dict))
+        let checksum = compiled::source_checksum(&[word_list.as_bytes()]);
+        if let Some(dict) = self.get_compiled_dictionary(&repo.name, &checksum, || {
+            compiled::compile_word_list(&word_list)
+        }) {
+            return Some(Arc::new(dict));
+        }
+        Some(Arc::new(TextDictionary::new(&word_list)))
+    }
+
+    /// Load a compiled dictionary from the cache, compiling it first if there is
+    /// none for these sources yet. Returns None if compiling fails, so callers can
+    /// fall back to parsing the sources directly.
+    fn get_compiled_dictionary(
+        &self,
+        name: &str,
+        checksum: &str,
+        compile: impl FnOnce() -> Result<Vec<u8>, Box<dyn Error>>,
+    ) -> Option<CompiledDictionary> {
+        let hash = checksum.strip_prefix("sha256:").unwrap_or(checksum);
+        let file_name = format!(
+            "{}-{}.{}",
+            name,
+            &hash[..hash.len().min(16)],
+            compiled::EXTENSION
+        );
+        let path = self.compiled_dir.join(file_name);
+        if path.exists() {
+            match CompiledDictionary::open(&path) {
+                Ok(dict) if dict.source_checksum() == checksum => return Some(dict),
+                Ok(_) => debug!("Compiled dictionary is out of date: {:?}", path),
+                Err(e) => debug!("Failed to open compiled dictionary {:?}: {}", path, e),
+            }
+        }
+
+        info!("Compiling dictionary: {}", name);
+        let bytes = compile()
+            .inspect_err(|e| error!("Failed to compile dictionary {}: {}", name, e))
+            .ok()?;
+        if let Err(e) = compiled::write(&path, &bytes) {
+            error!("Failed to write compiled dictionary {:?}: {}", path, e);
+            return None;
+        }
+        self.remove_stale_compiled(name, &path);
+        CompiledDictionary::open(&path)
+            .inspect_err(|e| error!("Failed to open compiled dictionary {:?}: {}", path, e))
+            .ok()
+    }
+
+    /// Remove dictionaries compiled from older sources of `name`.
+    fn remove_stale_compiled(&self, name: &str, current: &Path) {
+        let Ok(entries) = std::fs::read_dir(&self.compiled_dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_stale = path != current
+                && path.extension().is_some_and(|e| e == compiled::EXTENSION)
+                && path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .and_then(|s| s.rsplit_once('-'))
+                    .is_some_and(|(stem_name, _)| stem_name == name);
+            if is_stale {
+                debug!("Removing stale compiled dictionary: {:?}", path);
+                let _ = std::fs::remove_file(path);
+            }
+        }
     }
 }
@@ -1,3 +1,6 @@
+mod affix;
+pub mod bundled;
+pub mod compiled;
 pub mod dictionary;
 pub mod manager;
 pub mod repo;
@@ -7,6 +7,9 @@ pub struct HunspellRepo {
     pub aff_url: String,
     pub dict_url: String,
     pub name: String,
+    /// Expected SHA-256 hashes of the downloads, if they are pinned
+    pub aff_sha256: Option<String>,
+    pub dict_sha256: Option<String>,
 }
 
 impl HunspellRepo {
@@ -15,8 +18,19 @@ impl HunspellRepo {
             aff_url: aff_url.to_string(),
             dict_url: dict_url.to_string(),
             name: name.to_string(),
+            aff_sha256: None,
+            dict_sha256: None,
         }
     }
+
+    /// Reject downloads that don't have these SHA-256 hashes. Only for URLs
+    /// pinned to a commit, files on a branch change.
+    pub fn with_checksums(mut self, aff_sha256: &str, dict_sha256: &str) -> Self {
+        debug_assert!(is_immutable_url(&self.aff_url) && is_immutable_url(&self.dict_url));
+        self.aff_sha256 = Some(aff_sha256.to_string());
+        self.dict_sha256 = Some(dict_sha256.to_string());
+        self
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -24,6 +38,8 @@ pub struct TextRepo {
     pub url: Option<String>,
     pub text: Option<&'static str>,
     pub name: String,
+    /// Expected SHA-256 hash of the download, if it is pinned
+    pub sha256: Option<String>,
 }
 
 impl TextRepo {
@@ -32,10 +48,18 @@ impl TextRepo {
             url: Some(url.to_string()),
             text: None,
             name: name.to_string(),
+            sha256: None,
         }
     }
 }
 
+/// Whether a URL points at a fixed commit, like `.../<40 hex digits>/file.dic`,
+/// rather than a branch whose files can change
+pub fn is_immutable_url(url: &str) -> bool {
+    url.split('/')
+        .any(|segment| segment.len() == 40 && segment.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
 #[derive(Clone, Debug)]
 pub enum DictionaryRepo {
     Hunspell(HunspellRepo),
@@ -120,6 +144,7 @@ static TEXT_DICTIONARIES: LazyLock<Vec<TextRepo>> = LazyLock::new(|| {
             name: "codebook".to_string(),
             text: Some(CODEBOOK_DICTIONARY),
             url: None,
+            sha256: None,
         },
     ]
 });
@@ -136,6 +161,15 @@ pub fn get_repo(name: &str) -> Option<DictionaryRepo> {
     None
 }
 
+/// IDs of all dictionaries that can be used in `dictionaries`
+pub fn get_dictionary_ids() -> Vec<String> {
+    HUNSPELL_DICTIONARIES
+        .iter()
+        .map(|d| d.name.clone())
+        .chain(TEXT_DICTIONARIES.iter().map(|d| d.name.clone()))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,4 +234,21 @@ mod tests {
             non_snake_case
         );
     }
+
+    #[test]
+    fn test_checksums_only_for_immutable_urls() {
+        assert!(is_immutable_url(
+            "https://raw.githubusercontent.com/a/b/0123456789abcdef0123456789abcdef01234567/x.dic"
+        ));
+        assert!(!is_immutable_url(
+            "https://raw.githubusercontent.com/a/b/refs/heads/main/x.dic"
+        ));
+        for repo in HUNSPELL_DICTIONARIES.iter() {
+            assert!(repo.aff_sha256.is_none() || is_immutable_url(&repo.aff_url));
+            assert!(repo.dict_sha256.is_none() || is_immutable_url(&repo.dict_url));
+        }
+        for repo in TEXT_DICTIONARIES.iter() {
+            assert!(repo.sha256.is_none() || repo.url.as_deref().is_some_and(is_immutable_url));
+        }
+    }
 }
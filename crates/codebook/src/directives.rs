@@ -0,0 +1,329 @@
+use crate::parser::WordLocation;
+use crate::queries::LanguageType;
+use std::collections::HashSet;
+
+static DIRECTIVE_PREFIX: &str = "codebook:";
+
+/// Suppressions from `codebook:` directives in the comments of a file:
+/// - `codebook:ignore-line` skips the lines of the comment
+/// - `codebook:ignore-next-line` skips the line after the comment
+/// - `codebook:disable` skips everything until `codebook:enable`
+/// - `codebook:words foo bar` allows words in the whole file
+#[derive(Debug, Default)]
+pub(crate) struct Directives {
+    /// Comments with their first and last line, see `add_comment`
+    comments: Vec<(u32, u32, String)>,
+}
+
+#[derive(Debug, Default)]
+struct Suppressions {
+    ignored_lines: HashSet<u32>,
+    /// Disabled lines, from the first up to and including the last
+    disabled: Vec<(u32, u32)>,
+    words: HashSet<String>,
+}
+
+impl Directives {
+    /// Directives of plain text, where any line can hold them
+    pub(crate) fn from_text(text: &str) -> Self {
+        let mut directives = Self::default();
+        for (line_number, line) in text.lines().enumerate() {
+            directives.add_comment(line, line_number as u32);
+        }
+        directives
+    }
+
+    /// Add a comment starting on `first_line`
+    pub(crate) fn add_comment(&mut self, text: &str, first_line: u32) {
+        if text.contains(DIRECTIVE_PREFIX) {
+            let last_line = first_line + text.lines().count().saturating_sub(1) as u32;
+            self.comments
+                .push((first_line, last_line, text.to_string()));
+        }
+    }
+
+    /// Remove the locations and words suppressed by the directives
+    pub(crate) fn apply(&self, word_locations: Vec<WordLocation>) -> Vec<WordLocation> {
+        if self.comments.is_empty() {
+            return word_locations;
+        }
+        let suppressions = self.suppressions();
+        word_locations
+            .into_iter()
+            .filter(|location| {
+                !suppressions
+                    .words
+                    .contains(&location.word.to_ascii_lowercase())
+            })
+            .filter_map(|mut location| {
+                location.locations.retain(|range| {
+                    !suppressions.ignored_lines.contains(&range.line)
+                        && !suppressions
+                            .disabled
+                            .iter()
+                            .any(|(first, last)| (*first..=*last).contains(&range.line))
+                });
+                (!location.locations.is_empty()).then_some(location)
+            })
+            .collect()
+    }
+
+    fn suppressions(&self) -> Suppressions {
+        let mut comments: Vec<&(u32, u32, String)> = self.comments.iter().collect();
+        comments.sort_by_key(|(first_line, _, _)| *first_line);
+
+        let mut suppressions = Suppressions::default();
+        let mut disabled_from = None;
+        for (first_line, last_line, text) in comments {
+            for (directive, arguments) in parse_directives(text) {
+                match directive {
+                    "ignore-line" => suppressions.ignored_lines.extend(*first_line..=*last_line),
+                    "ignore-next-line" => {
+                        suppressions.ignored_lines.insert(last_line + 1);
+                    }
+                    "disable" => {
+                        disabled_from.get_or_insert(*first_line);
+                    }
+                    "enable" => {
+                        if let Some(from) = disabled_from.take() {
+                            suppressions.disabled.push((from, *last_line));
+                        }
+                    }
+                    "words" => suppressions.words.extend(
+                        arguments
+                            .split_whitespace()
+                            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+                            .filter(|word| !word.is_empty())
+                            .map(|word| word.to_ascii_lowercase()),
+                    ),
+                    _ => {}
+                }
+            }
+        }
+        if let Some(from) = disabled_from {
+            suppressions.disabled.push((from, u32::MAX));
+        }
+        suppressions
+    }
+}
+
+/// Text to insert at a position of a file, see `add_word_directive`
+#[derive(Debug, PartialEq)]
+pub struct DirectiveEdit {
+    pub line: u32,
+    /// Position in the line in characters
+    pub character: u32,
+    pub new_text: String,
+}
+
+/// Add a word to the `codebook:words` directive of a file, or insert one in a
+/// comment at the top of the file, after any `#!` or `<?php` line or YAML
+/// front matter.
+pub fn add_word_directive(text: &str, language: LanguageType, word: &str) -> DirectiveEdit {
+    let words_directive = format!("{}words", DIRECTIVE_PREFIX);
+    let (comment_start, comment_end) = language.comment_delimiters();
+    if let Some((line_number, line)) = text.lines().enumerate().find(|(_, line)| {
+        line.contains(&words_directive) && is_comment_line(line, language, comment_start)
+    }) {
+        // Add the word after the last one, before the end of the comment
+        let mut end = line.trim_end();
+        for comment_end in ["-->", "*/"] {
+            end = end.strip_suffix(comment_end).unwrap_or(end).trim_end();
+        }
+        return DirectiveEdit {
+            line: line_number as u32,
+            character: end.chars().count() as u32,
+            new_text: format!(" {}", word),
+        };
+    }
+
+    let directive = format!(
+        "{}{} {}{}",
+        comment_start, words_directive, word, comment_end
+    );
+    let header_lines = header_lines(text, language);
+    if header_lines == 0 {
+        return DirectiveEdit {
+            line: 0,
+            character: 0,
+            new_text: format!("{}\n", directive),
+        };
+    }
+    let last_header = text.split_inclusive('\n').nth(header_lines - 1);
+    match last_header {
+        Some(line) if !line.ends_with('\n') => DirectiveEdit {
+            line: header_lines as u32 - 1,
+            character: line.chars().count() as u32,
+            new_text: format!("\n{}", directive),
+        },
+        _ => DirectiveEdit {
+            line: header_lines as u32,
+            character: 0,
+            new_text: format!("{}\n", directive),
+        },
+    }
+}
+
+/// Whether a line is a comment that can hold directives. Any line of plain
+/// text can, in code they must start with a comment, not e.g. a string.
+fn is_comment_line(line: &str, language: LanguageType, comment_start: &str) -> bool {
+    language == LanguageType::Text || line.trim_start().starts_with(comment_start.trim_end())
+}
+
+/// Number of lines at the top of a file that must stay first: a `#!` or
+/// `<?php` line, or the YAML front matter of a text file.
+fn header_lines(text: &str, language: LanguageType) -> usize {
+    let mut lines = text.lines();
+    match lines.next() {
+        Some(first) if first.starts_with("#!") || first.starts_with("<?php") => 1,
+        Some(first) if language == LanguageType::Text && first.trim_end() == "---" => lines
+            .position(|line| matches!(line.trim_end(), "---" | "..."))
+            .map_or(0, |index| index + 2),
+        _ => 0,
+    }
+}
+
+/// Find each directive in a comment with the rest of its line
+fn parse_directives(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    text.lines().flat_map(|line| {
+        line.match_indices(DIRECTIVE_PREFIX).map(move |(index, _)| {
+            let rest = &line[index + DIRECTIVE_PREFIX.len()..];
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+                .unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::TextRange;
+
+    fn location(word: &str, lines: &[u32]) -> WordLocation {
+        WordLocation::new(
+            word.to_string(),
+            lines
+                .iter()
+                .map(|line| TextRange {
+                    start_char: 0,
+                    end_char: word.len() as u32,
+                    line: *line,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_directives() {
+        let text = "\
+wrng codebook:ignore-line
+// codebook:ignore-next-line
+wrng
+wrng
+/* codebook:disable */
+wrng
+// codebook:enable codebook:words Foo bar*/
+wrng foo
+<!-- codebook:disable -->
+wrng
+";
+        let directives = Directives::from_text(text);
+        let results = directives.apply(vec![
+            location("wrng", &[0, 2, 3, 5, 7, 9]),
+            location("foo", &[7]),
+        ]);
+        assert_eq!(results, vec![location("wrng", &[3, 7])]);
+    }
+
+    #[test]
+    fn test_add_word_directive() {
+        let edit = |text, language| add_word_directive(text, language, "wrng");
+        assert_eq!(
+            edit("fn main() {}\n", LanguageType::Rust),
+            DirectiveEdit {
+                line: 0,
+                character: 0,
+                new_text: "// codebook:words wrng\n".to_string(),
+            }
+        );
+        assert_eq!(
+            edit("#!/bin/sh\necho\n", LanguageType::Bash).new_text,
+            "# codebook:words wrng\n"
+        );
+        assert_eq!(edit("#!/bin/sh\necho\n", LanguageType::Bash).line, 1);
+        assert_eq!(
+            edit("<?php", LanguageType::Php),
+            DirectiveEdit {
+                line: 0,
+                character: 5,
+                new_text: "\n// codebook:words wrng".to_string(),
+            }
+        );
+        // Words are added to an existing directive
+        assert_eq!(
+            edit("# Title\n<!-- codebook:words foo -->\n", LanguageType::Text),
+            DirectiveEdit {
+                line: 1,
+                character: 23,
+                new_text: " wrng".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_add_word_directive_after_front_matter() {
+        let text = "---\ntitle: Notes\n---\n# Notes\n";
+        assert_eq!(
+            add_word_directive(text, LanguageType::Text, "wrng"),
+            DirectiveEdit {
+                line: 3,
+                character: 0,
+                new_text: "<!-- codebook:words wrng -->\n".to_string(),
+            }
+        );
+        assert_eq!(
+            add_word_directive("---\ntitle: Notes\n---", LanguageType::Text, "wrng"),
+            DirectiveEdit {
+                line: 2,
+                character: 3,
+                new_text: "\n<!-- codebook:words wrng -->".to_string(),
+            }
+        );
+        // An unclosed block isn't front matter
+        assert_eq!(
+            add_word_directive("---\ntext\n", LanguageType::Text, "wrng").line,
+            0
+        );
+    }
+
+    #[test]
+    fn test_add_word_directive_ignores_strings() {
+        let text = "let s = \"codebook:words foo\";\n// codebook:words bar\n";
+        assert_eq!(
+            add_word_directive(text, LanguageType::Rust, "wrng"),
+            DirectiveEdit {
+                line: 1,
+                character: 21,
+                new_text: " wrng".to_string(),
+            }
+        );
+        let text = "let s = \"codebook:words foo\";\n";
+        assert_eq!(
+            add_word_directive(text, LanguageType::Rust, "wrng"),
+            DirectiveEdit {
+                line: 0,
+                character: 0,
+                new_text: "// codebook:words wrng\n".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        let directives: Vec<_> =
+            parse_directives("# codebook:words a b\n# codebook:enable").collect();
+        assert_eq!(directives, vec![("words", " a b"), ("enable", "")]);
+    }
+}
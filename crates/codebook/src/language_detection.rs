@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use crate::dictionaries::dictionary::Dictionary;
+
+/// Regions with fewer words than this are too short to tell their language.
+const MIN_REGION_WORDS: usize = 3;
+
+/// Words shorter than this are never spell checked, so they don't count either.
+const MIN_WORD_LENGTH: usize = 3;
+
+/// Pick the dictionaries that fit a region of text best, i.e. that know the most
+/// of its words. Regions that are too short to tell, or that fit several
+/// dictionaries equally well, keep all of them.
+pub fn select_dictionaries(
+    words: &[&str],
+    dictionaries: &[Arc<dyn Dictionary>],
+) -> Vec<Arc<dyn Dictionary>> {
+    let words: Vec<&str> = words
+        .iter()
+        .filter(|w| w.chars().count() >= MIN_WORD_LENGTH)
+        .copied()
+        .collect();
+    if dictionaries.len() < 2 || words.len() < MIN_REGION_WORDS {
+        return dictionaries.to_vec();
+    }
+    let scores: Vec<usize> = dictionaries
+        .iter()
+        .map(|d| words.iter().filter(|w| d.check(w)).count())
+        .collect();
+    let best = scores.iter().copied().max().unwrap_or(0);
+    if best == 0 {
+        return dictionaries.to_vec();
+    }
+    dictionaries
+        .iter()
+        .zip(scores)
+        .filter(|(_, score)| *score == best)
+        .map(|(d, _)| d.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dictionaries::dictionary::TextDictionary;
+
+    fn dictionaries() -> Vec<Arc<dyn Dictionary>> {
+        vec![
+            Arc::new(TextDictionary::new("the\nhouse\nis\nbig\nsmall")),
+            Arc::new(TextDictionary::new("das\nhaus\nist\ngross\nklein")),
+        ]
+    }
+
+    #[test]
+    fn test_selects_best_fit() {
+        let dictionaries = dictionaries();
+        let selected = select_dictionaries(&["das", "Haus", "ist", "grosss"], &dictionaries);
+        assert_eq!(selected.len(), 1);
+        assert!(selected[0].check("haus"));
+
+        let selected = select_dictionaries(&["the", "house", "is", "smal"], &dictionaries);
+        assert_eq!(selected.len(), 1);
+        assert!(selected[0].check("house"));
+    }
+
+    #[test]
+    fn test_short_regions_keep_all() {
+        let dictionaries = dictionaries();
+        assert_eq!(select_dictionaries(&["haus"], &dictionaries).len(), 2);
+        // Short words don't count
+        assert_eq!(
+            select_dictionaries(&["is", "das", "haus"], &dictionaries).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_unknown_regions_keep_all() {
+        let dictionaries = dictionaries();
+        assert_eq!(
+            select_dictionaries(&["foo", "bar", "baz"], &dictionaries).len(),
+            2
+        );
+    }
+}
@@ -1,31 +1,64 @@
+pub mod casing;
 pub mod dictionaries;
+pub mod directives;
+mod language_detection;
 mod logging;
 pub mod parser;
 pub mod queries;
 pub mod regexes;
 mod splitter;
+mod suggestions;
 
 use regexes::get_default_skip_patterns;
+use std::path::Path;
 use std::sync::Arc;
 
-use codebook_config::CodebookConfig;
+use codebook_config::{CodebookConfig, DirectoryConfig};
 use dictionaries::{dictionary, manager::DictionaryManager};
 use dictionary::Dictionary;
 use log::debug;
 use parser::WordLocation;
+use suggestions::SuggestionContext;
 
 pub struct Codebook {
     config: Arc<CodebookConfig>,
-    manager: DictionaryManager,
+    manager: Arc<DictionaryManager>,
+}
+
+/// Why a word was flagged, see `Codebook::explain_word`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordExplanation {
+    /// Words the identifier holding the word was split into
+    pub parts: Vec<String>,
+    /// IDs of the dictionaries the word was checked against
+    pub dictionaries: Vec<String>,
+    /// Whether the word matched `flag_words`
+    pub flagged: bool,
+    /// The configured casing, if the word was flagged for its casing
+    pub correct_casing: Option<String>,
+    pub suggestions: Vec<String>,
 }
 
 // Custom 'codebook' dictionary could be removed later for a more general solution.
 static DEFAULT_DICTIONARIES: &[&str; 3] = &["codebook", "software_terms", "computing_acronyms"];
+static PROGRAMMING_TERMS_DICTIONARY: &str = "codebook";
 
 impl Codebook {
     pub fn new(config: Arc<CodebookConfig>) -> Result<Self, Box<dyn std::error::Error>> {
-        let manager = DictionaryManager::new(&config.cache_dir);
-        Ok(Self { config, manager })
+        let manager = DictionaryManager::new(&config.cache_dir, config.get_downloader_options());
+        Ok(Self {
+            config,
+            manager: Arc::new(manager),
+        })
+    }
+
+    /// A spell checker for another config, sharing the dictionaries of this
+    /// one. Dictionaries keep being downloaded with this config's options.
+    pub fn with_config(&self, config: Arc<CodebookConfig>) -> Self {
+        Self {
+            config,
+            manager: Arc::clone(&self.manager),
+        }
     }
 
     /// Get WordLocations for a block of text.
@@ -36,38 +69,52 @@ impl Codebook {
         language: Option<queries::LanguageType>,
         file_path: Option<&str>,
     ) -> Vec<parser::WordLocation> {
-        if file_path.is_some() && self.config.should_ignore_path(file_path.unwrap()) {
+        let language = self.resolve_language(language, file_path);
+        let config = self
+            .config
+            .config_for_file(file_path.map(Path::new), language.ids());
+        if file_path.is_some() && config.should_ignore_path(file_path.unwrap()) {
             return Vec::new();
         }
         // get needed dictionary names
         // get needed dictionaries
         // call spell check on each dictionary
-        let language = self.resolve_language(language, file_path);
-        let dictionaries = self.get_dictionaries(Some(language));
+        let word_dictionaries = self.get_word_dictionaries(&config, file_path);
+        let support_dictionaries = self.get_support_dictionaries(Some(language));
         let mut regex_patterns = get_default_skip_patterns().clone();
-        if let Some(config_patterns) = self.config.get_ignore_patterns() {
-            regex_patterns.extend(config_patterns);
+        regex_patterns.extend(config.get_ignore_patterns().iter().cloned());
+        let check_word = |word: &str, word_dictionaries: &[Arc<dyn Dictionary>]| {
+            if config.should_flag_word(word) || config.get_correct_casing(word).is_some() {
+                return false;
+            }
+            if word.len() < 3 {
+                return true;
+            }
+            if config.is_allowed_word(word) {
+                return true;
+            }
+            word_dictionaries
+                .iter()
+                .chain(&support_dictionaries)
+                .any(|dictionary| dictionary.check(word))
+        };
+        if config.should_detect_language() && word_dictionaries.len() > 1 {
+            // Check each comment or paragraph against the language it is written in
+            return parser::find_locations_by_region(
+                text,
+                language,
+                |words| {
+                    let selected =
+                        language_detection::select_dictionaries(words, &word_dictionaries);
+                    move |word: &str| check_word(word, &selected)
+                },
+                &regex_patterns,
+            );
         }
         parser::find_locations(
             text,
             language,
-            |word| {
-                if self.config.should_flag_word(word) {
-                    return false;
-                }
-                if word.len() < 3 {
-                    return true;
-                }
-                if self.config.is_allowed_word(word) {
-                    return true;
-                }
-                for dictionary in &dictionaries {
-                    if dictionary.check(word) {
-                        return true;
-                    }
-                }
-                false
-            },
+            |word| check_word(word, &word_dictionaries),
             &regex_patterns,
         )
     }
@@ -87,16 +134,24 @@ impl Codebook {
         }
     }
 
-    fn get_dictionaries(
+    /// Dictionaries of the natural languages configured for a file.
+    fn get_word_dictionaries(
+        &self,
+        config: &DirectoryConfig,
+        file_path: Option<&str>,
+    ) -> Vec<Arc<dyn Dictionary>> {
+        self.load_dictionaries(word_dictionary_ids(config, file_path))
+    }
+
+    /// Dictionaries of programming terms, used alongside every natural language.
+    fn get_support_dictionaries(
         &self,
         language: Option<queries::LanguageType>,
     ) -> Vec<Arc<dyn Dictionary>> {
-        let mut dictionary_ids = self.config.get_dictionary_ids();
-        if let Some(lang) = language {
-            let language_dictionary_ids = lang.dictionary_ids();
-            dictionary_ids.extend(language_dictionary_ids);
-        };
-        dictionary_ids.extend(DEFAULT_DICTIONARIES.iter().map(|f| f.to_string()));
+        self.load_dictionaries(support_dictionary_ids(language))
+    }
+
+    fn load_dictionaries(&self, dictionary_ids: Vec<String>) -> Vec<Arc<dyn Dictionary>> {
         let mut dictionaries = Vec::with_capacity(dictionary_ids.len());
         debug!("Checking text with dictionaries: {:?}", dictionary_ids);
         for dictionary_id in dictionary_ids {
@@ -108,16 +163,126 @@ impl Codebook {
         dictionaries
     }
 
+    /// Load the dictionaries every file may need, so the first check doesn't
+    /// wait for downloads. `on_progress` is called before each dictionary with
+    /// its ID, its index and the number of dictionaries.
+    /// Returns each dictionary ID with whether it could be loaded.
+    pub fn preload_dictionaries(
+        &self,
+        on_progress: impl FnMut(&str, usize, usize),
+    ) -> Vec<(String, bool)> {
+        let mut dictionary_ids = self.config.get_all_dictionary_ids();
+        dictionary_ids.extend(DEFAULT_DICTIONARIES.iter().map(|id| id.to_string()));
+        self.load_dictionary_ids(dictionary_ids, on_progress)
+    }
+
+    /// Load every dictionary the configuration or a supported language may
+    /// need, so they are downloaded and compiled before going offline.
+    /// Returns each dictionary ID with whether it could be loaded.
+    pub fn prefetch_dictionaries(&self) -> Vec<(String, bool)> {
+        let mut dictionary_ids = self.config.get_all_dictionary_ids();
+        dictionary_ids.extend(
+            queries::LANGUAGE_SETTINGS
+                .iter()
+                .flat_map(|setting| setting.dictionary_ids.iter().map(|id| id.to_string())),
+        );
+        dictionary_ids.extend(DEFAULT_DICTIONARIES.iter().map(|id| id.to_string()));
+        self.load_dictionary_ids(dictionary_ids, |_, _, _| {})
+    }
+
+    fn load_dictionary_ids(
+        &self,
+        mut dictionary_ids: Vec<String>,
+        mut on_progress: impl FnMut(&str, usize, usize),
+    ) -> Vec<(String, bool)> {
+        dictionary_ids.sort();
+        dictionary_ids.dedup();
+        let total = dictionary_ids.len();
+        dictionary_ids
+            .into_iter()
+            .enumerate()
+            .map(|(index, id)| {
+                on_progress(&id, index, total);
+                let loaded = self.manager.get_dictionary(&id).is_some();
+                (id, loaded)
+            })
+            .collect()
+    }
+
     pub fn spell_check_file(&self, path: &str) -> Vec<WordLocation> {
         let lang_type = queries::get_language_name_from_filename(path);
         let file_text = std::fs::read_to_string(path).unwrap();
         self.spell_check(&file_text, Some(lang_type), Some(path))
     }
 
-    pub fn get_suggestions(&self, word: &str) -> Option<Vec<String>> {
-        // Get top suggestions and return the first 5 suggestions in round robin order
-        let max_results = 5;
-        let dictionaries = self.get_dictionaries(None);
+    /// Get the configured casing of a word flagged only for being written with
+    /// other casing than a case-sensitive word of the file's config.
+    pub fn get_correct_casing(&self, word: &str, file_path: Option<&str>) -> Option<String> {
+        let language = self.resolve_language(None, file_path);
+        let config = self
+            .config
+            .config_for_file(file_path.map(Path::new), language.ids());
+        config.get_correct_casing(word).map(str::to_string)
+    }
+
+    /// Whether a word is flagged by the config of a file, rather than unknown.
+    pub fn should_flag_word(&self, word: &str, file_path: Option<&str>) -> bool {
+        let language = self.resolve_language(None, file_path);
+        self.config
+            .config_for_file(file_path.map(Path::new), language.ids())
+            .should_flag_word(word)
+    }
+
+    /// Explain why a word of a file was flagged: how the identifier holding it
+    /// was split, which dictionaries were consulted, whether the config flags
+    /// it and what it could be replaced with.
+    pub fn explain_word(
+        &self,
+        word: &str,
+        identifier: &str,
+        language: Option<queries::LanguageType>,
+        document: Option<&str>,
+        file_path: Option<&str>,
+    ) -> WordExplanation {
+        let language = self.resolve_language(language, file_path);
+        let config = self
+            .config
+            .config_for_file(file_path.map(Path::new), language.ids());
+        let dictionaries = word_dictionary_ids(&config, file_path)
+            .into_iter()
+            .chain(support_dictionary_ids(Some(language)))
+            .filter(|id| self.manager.get_dictionary(id).is_some())
+            .collect();
+        WordExplanation {
+            parts: parser::split_identifier(identifier),
+            dictionaries,
+            flagged: config.should_flag_word(word),
+            correct_casing: config.get_correct_casing(word).map(str::to_string),
+            suggestions: self
+                .get_suggestions(word, document, file_path)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Get ranked suggestions for a misspelled word.
+    /// Supply the text of the document the word came from to prefer words already used in it,
+    /// and its path to use the dictionaries configured for it.
+    pub fn get_suggestions(
+        &self,
+        word: &str,
+        document: Option<&str>,
+        file_path: Option<&str>,
+    ) -> Option<Vec<String>> {
+        let language = self.resolve_language(None, file_path);
+        let config = self
+            .config
+            .config_for_file(file_path.map(Path::new), language.ids());
+        if let Some(casing) = config.get_correct_casing(word) {
+            return Some(vec![casing.to_string()]);
+        }
+        let max_results = config.get_max_suggestions();
+        let mut dictionaries = self.get_word_dictionaries(&config, file_path);
+        dictionaries.extend(self.get_support_dictionaries(None));
         let mut is_misspelled = false;
         let suggestions: Vec<Vec<String>> = dictionaries
             .iter()
@@ -133,137 +298,39 @@ impl Codebook {
         if !is_misspelled {
             return None;
         }
-        Some(collect_round_robin(&suggestions, max_results))
+        // Candidates in the built-in list of words used in code, like keywords
+        // and API names, get a bonus. It's not a frequency list, it only tells
+        // known programming terms apart.
+        let programming_terms = self.manager.get_dictionary(PROGRAMMING_TERMS_DICTIONARY);
+        let context = SuggestionContext::new(document);
+        Some(suggestions::rank_suggestions(
+            word,
+            &suggestions,
+            &context,
+            |candidate| {
+                programming_terms
+                    .as_ref()
+                    .is_some_and(|d| d.check(candidate))
+            },
+            max_results,
+        ))
     }
 }
 
-fn collect_round_robin<T: Clone + PartialEq + Ord>(sources: &[Vec<T>], max_count: usize) -> Vec<T> {
-    let mut result = Vec::with_capacity(max_count);
-    for i in 0..max_count {
-        for source in sources {
-            if let Some(item) = source.get(i) {
-                if !result.contains(item) {
-                    result.push(item.clone());
-                    if result.len() >= max_count {
-                        return result;
-                    }
-                }
-            }
-        }
+/// IDs of the natural language dictionaries configured for a file.
+fn word_dictionary_ids(config: &DirectoryConfig, file_path: Option<&str>) -> Vec<String> {
+    match file_path {
+        Some(path) => config.get_dictionary_ids_for_path(path),
+        None => config.get_dictionary_ids(),
     }
-    result.sort();
-    result
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_collect_round_robin_basic() {
-        let sources = vec![
-            vec!["apple", "banana", "cherry"],
-            vec!["date", "elderberry", "fig"],
-            vec!["grape", "honeydew", "kiwi"],
-        ];
-
-        let result = collect_round_robin(&sources, 5);
-        // Round-robin order: first from each source, then second from each source
-        assert_eq!(
-            result,
-            vec!["apple", "date", "grape", "banana", "elderberry"]
-        );
-    }
-
-    #[test]
-    fn test_collect_round_robin_with_duplicates() {
-        let sources = vec![
-            vec!["apple", "banana", "cherry"],
-            vec!["banana", "cherry", "date"],
-            vec!["cherry", "date", "elderberry"],
-        ];
-
-        // In round-robin, we get:
-        // 1. apple (1st from 1st source)
-        // 2. banana (1st from 2nd source) - cherry already taken
-        // 3. cherry (1st from 3rd source)
-        // 4. banana (2nd from 1st source)
-        // 5. date (3rd from 2nd source) - cherry already taken
-        let result = collect_round_robin(&sources, 5);
-        assert_eq!(
-            result,
-            vec!["apple", "banana", "cherry", "date", "elderberry"]
-        );
-    }
-
-    #[test]
-    fn test_collect_round_robin_uneven_sources() {
-        let sources = vec![
-            vec!["apple", "banana", "cherry", "date"],
-            vec!["elderberry"],
-            vec!["fig", "grape"],
-        ];
-
-        // Round-robin order with uneven sources
-        let result = collect_round_robin(&sources, 7);
-        assert_eq!(
-            result,
-            vec![
-                "apple",
-                "elderberry",
-                "fig",
-                "banana",
-                "grape",
-                "cherry",
-                "date"
-            ]
-        );
-    }
-
-    #[test]
-    fn test_collect_round_robin_empty_sources() {
-        let sources: Vec<Vec<&str>> = vec![];
-        let result = collect_round_robin(&sources, 5);
-        assert_eq!(result, Vec::<&str>::new());
-    }
-
-    #[test]
-    fn test_collect_round_robin_some_empty_sources() {
-        let sources = vec![vec!["apple", "banana"], vec![], vec!["cherry", "date"]];
-
-        // Round-robin order, skipping empty source
-        let result = collect_round_robin(&sources, 4);
-        assert_eq!(result, vec!["apple", "cherry", "banana", "date"]);
-    }
-
-    #[test]
-    fn test_collect_round_robin_with_numbers() {
-        let sources = vec![vec![1, 3, 5], vec![2, 4, 6]];
-
-        // Round-robin order with numbers
-        let result = collect_round_robin(&sources, 6);
-        assert_eq!(result, vec![1, 2, 3, 4, 5, 6]);
-    }
-
-    #[test]
-    fn test_collect_round_robin_max_count_exceeded() {
-        let sources = vec![
-            vec!["apple", "banana", "cherry"],
-            vec!["date", "elderberry", "fig"],
-            vec!["grape", "honeydew", "kiwi"],
-        ];
-
-        // First round of round-robin (first from each source)
-        let result = collect_round_robin(&sources, 3);
-        assert_eq!(result, vec!["apple", "date", "grape"]);
-    }
-
-    #[test]
-    fn test_collect_round_robin_max_count_higher_than_available() {
-        let sources = vec![vec!["apple", "banana"], vec!["cherry", "date"]];
-
-        // Round-robin order for all available elements
-        let result = collect_round_robin(&sources, 10);
-        assert_eq!(result, vec!["apple", "banana", "cherry", "date"]);
-    }
+/// IDs of the programming term dictionaries for a language.
+fn support_dictionary_ids(language: Option<queries::LanguageType>) -> Vec<String> {
+    let mut dictionary_ids = Vec::new();
+    if let Some(lang) = language {
+        dictionary_ids.extend(lang.dictionary_ids());
+    };
+    dictionary_ids.extend(DEFAULT_DICTIONARIES.iter().map(|f| f.to_string()));
+    dictionary_ids
 }
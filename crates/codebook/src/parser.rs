@@ -1,3 +1,4 @@
+use crate::directives::Directives;
 use crate::splitter::{self};
 
 use crate::queries::{LanguageType, get_language_setting};
@@ -218,18 +219,83 @@ pub fn find_locations(
     match language {
         LanguageType::Text => {
             let processor = TextProcessor::new(text, skip_patterns);
-            processor.process_words_with_check(|word| check_function(word))
+            Directives::from_text(text)
+                .apply(processor.process_words_with_check(|word| check_function(word)))
         }
-        _ => find_locations_code(text, language, |word| check_function(word), skip_patterns),
+        _ => find_locations_code(text, language, |_| &check_function, skip_patterns),
     }
 }
 
-fn find_locations_code(
+/// Like `find_locations`, but builds a check function for each region of text
+/// (a comment or string in code, a paragraph in plain text) from the words in it.
+pub fn find_locations_by_region<F>(
     text: &str,
     language: LanguageType,
-    check_function: impl Fn(&str) -> bool,
+    region_check: impl Fn(&[&str]) -> F,
     skip_patterns: &[Regex],
-) -> Vec<WordLocation> {
+) -> Vec<WordLocation>
+where
+    F: Fn(&str) -> bool,
+{
+    match language {
+        LanguageType::Text => {
+            let mut word_locations: HashMap<String, Vec<TextRange>> = HashMap::new();
+            for (first_line, paragraph) in paragraphs(text) {
+                let processor = TextProcessor::new(&paragraph, skip_patterns);
+                let words = processor.extract_words();
+                let word_texts: Vec<&str> = words.iter().map(|(word, _)| word.as_str()).collect();
+                let check_function = region_check(&word_texts);
+                for location in processor.process_words_with_check(|word| check_function(word)) {
+                    word_locations.entry(location.word).or_default().extend(
+                        location.locations.into_iter().map(|range| TextRange {
+                            line: range.line + first_line,
+                            ..range
+                        }),
+                    );
+                }
+            }
+            Directives::from_text(text).apply(
+                word_locations
+                    .into_iter()
+                    .map(|(word, locations)| WordLocation::new(word, locations))
+                    .collect(),
+            )
+        }
+        _ => find_locations_code(text, language, region_check, skip_patterns),
+    }
+}
+
+/// Split text into paragraphs separated by blank lines, with the line each starts on.
+fn paragraphs(text: &str) -> Vec<(u32, String)> {
+    let mut paragraphs: Vec<(u32, String)> = Vec::new();
+    let mut current: Option<(u32, Vec<&str>)> = None;
+    for (line_number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            if let Some((first_line, lines)) = current.take() {
+                paragraphs.push((first_line, lines.join("\n")));
+            }
+        } else {
+            current
+                .get_or_insert_with(|| (line_number as u32, Vec::new()))
+                .1
+                .push(line);
+        }
+    }
+    if let Some((first_line, lines)) = current {
+        paragraphs.push((first_line, lines.join("\n")));
+    }
+    paragraphs
+}
+
+fn find_locations_code<F>(
+    text: &str,
+    language: LanguageType,
+    region_check: impl Fn(&[&str]) -> F,
+    skip_patterns: &[Regex],
+) -> Vec<WordLocation>
+where
+    F: Fn(&str) -> bool,
+{
     let language_setting =
         get_language_setting(language).expect("This _should_ never happen. Famous last words.");
     let mut parser = Parser::new();
@@ -242,6 +308,7 @@ fn find_locations_code(
     let query = Query::new(&language, language_setting.query).unwrap();
     let mut cursor = QueryCursor::new();
     let mut word_locations: HashMap<String, Vec<TextRange>> = HashMap::new();
+    let mut directives = Directives::default();
     let provider = text.as_bytes();
     let mut matches_query = cursor.matches(&query, root_node, provider);
 
@@ -252,8 +319,13 @@ fn find_locations_code(
             let node_start = node.start_position();
             let current_line = node_start.row as u32;
             let current_column = node_start.column as u32;
+            if query.capture_names()[capture.index as usize] == "comment" {
+                directives.add_comment(node_text, current_line);
+            }
             let processor = TextProcessor::new(node_text, skip_patterns);
             let words = processor.extract_words();
+            let word_texts: Vec<&str> = words.iter().map(|(word, _)| word.as_str()).collect();
+            let check_function = region_check(&word_texts);
             // debug!("Found Capture: {node_text:?}");
             // debug!("Words: {words:?}");
             // debug!("Column: {current_column}");
@@ -282,13 +354,15 @@ fn find_locations_code(
         }
     }
 
-    word_locations
-        .keys()
-        .map(|word| WordLocation {
-            word: word.clone(),
-            locations: word_locations.get(word).cloned().unwrap_or_default(),
-        })
-        .collect()
+    directives.apply(
+        word_locations
+            .keys()
+            .map(|word| WordLocation {
+                word: word.clone(),
+                locations: word_locations.get(word).cloned().unwrap_or_default(),
+            })
+            .collect(),
+    )
 }
 
 fn is_numeric(s: &str) -> bool {
@@ -304,6 +378,66 @@ pub fn get_word_from_string(start: usize, end: usize, text: &str) -> String {
     text.graphemes(true).skip(start).take(end - start).collect()
 }
 
+/// Get the word at a position in a line, counted in grapheme clusters. Words
+/// are split like identifiers, so the position in `fooBar` picks `foo` or `Bar`.
+pub fn get_word_at(character: usize, text: &str) -> Option<String> {
+    let (identifier, offset) = identifier_at(character, text)?;
+    splitter::split(&identifier)
+        .into_iter()
+        .take_while(|split| split.start_char <= offset)
+        .last()
+        .map(|split| trim_word(split.word))
+        .filter(|word| is_alphabetic(word))
+        .map(str::to_string)
+}
+
+/// Get the identifier at a position in a line, counted in grapheme clusters,
+/// such as `fooBar` or `calc_total`.
+pub fn get_identifier_at(character: usize, text: &str) -> Option<String> {
+    // Quotes around a string aren't part of it
+    identifier_at(character, text).map(|(identifier, _)| identifier.trim_matches('\'').to_string())
+}
+
+/// Split an identifier into the words that are checked, like `fooBar` into
+/// `foo` and `Bar`.
+pub fn split_identifier(identifier: &str) -> Vec<String> {
+    identifier
+        .split_whitespace()
+        .flat_map(splitter::split)
+        .map(|split| trim_word(split.word))
+        .filter(|word| is_alphabetic(word))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The identifier at a position with the position's offset in it in characters
+fn identifier_at(character: usize, text: &str) -> Option<(String, u32)> {
+    let is_word_part = |g: &str| {
+        g.chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '\'')
+    };
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    // The position may be just after the word
+    let position = (0..=character.min(graphemes.len()))
+        .rev()
+        .take(2)
+        .find(|i| graphemes.get(*i).is_some_and(|g| is_word_part(g)))?;
+    let start = graphemes[..position]
+        .iter()
+        .rposition(|g| !is_word_part(g))
+        .map_or(0, |i| i + 1);
+    let end = graphemes[position..]
+        .iter()
+        .position(|g| !is_word_part(g))
+        .map_or(graphemes.len(), |i| position + i);
+    let offset = graphemes[start..position].concat().chars().count() as u32;
+    Some((graphemes[start..end].concat(), offset))
+}
+
+fn trim_word(word: &str) -> &str {
+    word.trim_matches(|c| c == '_' || c == '\'')
+}
+
 #[cfg(test)]
 mod parser_tests {
     use super::*;
@@ -316,6 +450,41 @@ mod parser_tests {
         assert_eq!(results.len(), 4);
     }
 
+    #[test]
+    fn test_find_locations_by_region() {
+        let text = "first paragraf\nstill first\n\n  second paragraf";
+        // Only the second paragraph knows the word "paragraf"
+        let results = find_locations_by_region(
+            text,
+            LanguageType::Text,
+            |words| {
+                let second = words.contains(&"second");
+                move |word: &str| word != "paragraf" || second
+            },
+            &[],
+        );
+        assert_eq!(
+            results,
+            vec![WordLocation::new(
+                "paragraf".to_string(),
+                vec![TextRange {
+                    start_char: 6,
+                    end_char: 14,
+                    line: 0
+                }]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let text = "one\ntwo\n\n\nthree\n";
+        assert_eq!(
+            paragraphs(text),
+            vec![(0, "one\ntwo".to_string()), (4, "three".to_string())]
+        );
+    }
+
     #[test]
     fn test_get_words_from_text() {
         let text = r#"
@@ -379,6 +548,33 @@ mod parser_tests {
         let emoji_text = "Hello 👨‍👩‍👧‍👦 World";
         assert_eq!(get_word_from_string(6, 7, emoji_text), "👨‍👩‍👧‍👦");
     }
+    #[test]
+    fn test_get_word_at() {
+        let text = "let fooBar = \"don't\";";
+        assert_eq!(get_word_at(0, text).as_deref(), Some("let"));
+        assert_eq!(get_word_at(3, text).as_deref(), Some("let"));
+        assert_eq!(get_word_at(5, text).as_deref(), Some("foo"));
+        assert_eq!(get_word_at(8, text).as_deref(), Some("Bar"));
+        assert_eq!(get_word_at(15, text).as_deref(), Some("don't"));
+        assert_eq!(get_word_at(11, text), None);
+        assert_eq!(get_word_at(40, text), None);
+    }
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(get_identifier_at(2, "'don't'").as_deref(), Some("don't"));
+        let text = "let calc_wrldHTTPServer = 1;";
+        assert_eq!(
+            get_identifier_at(10, text).as_deref(),
+            Some("calc_wrldHTTPServer")
+        );
+        assert_eq!(
+            split_identifier("calc_wrldHTTPServer"),
+            vec!["calc", "wrld", "HTTP", "Server"]
+        );
+        assert_eq!(get_identifier_at(24, text), None);
+    }
+
     #[test]
     fn test_unicode_character_handling() {
         crate::logging::init_test_logging();
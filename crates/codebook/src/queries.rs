@@ -49,6 +49,36 @@ impl LanguageType {
         }
         vec![]
     }
+
+    /// Start and end of a single-line comment, for inserting directives.
+    /// Plain text uses HTML comments, which Markdown hides.
+    pub fn comment_delimiters(&self) -> (&'static str, &'static str) {
+        match self {
+            LanguageType::Bash
+            | LanguageType::Python
+            | LanguageType::R
+            | LanguageType::Ruby
+            | LanguageType::TOML => ("# ", ""),
+            LanguageType::C
+            | LanguageType::Go
+            | LanguageType::Java
+            | LanguageType::Javascript
+            | LanguageType::Php
+            | LanguageType::Rust
+            | LanguageType::Typescript => ("// ", ""),
+            LanguageType::Css => ("/* ", " */"),
+            LanguageType::Haskell => ("-- ", ""),
+            LanguageType::HTML | LanguageType::Text => ("<!-- ", " -->"),
+        }
+    }
+
+    /// Language IDs of this language, as used to select it in the config
+    pub fn ids(&self) -> &'static [&'static str] {
+        LANGUAGE_SETTINGS
+            .iter()
+            .find(|language| self == &language.type_)
+            .map_or(&["text"], |language| language.ids)
+    }
 }
 
 // Language ids documented at https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#textDocumentItem
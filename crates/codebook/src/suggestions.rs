@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use crate::casing::WordCase;
+use crate::splitter;
+
+// Score weights. The distance term dominates; everything else nudges
+// candidates that are equally close to the misspelled word.
+const DICTIONARY_RANK_WEIGHT: f64 = 0.05;
+const MULTIPLE_SOURCES_BONUS: f64 = 0.1;
+const KNOWN_TERM_BONUS: f64 = 0.4;
+const IN_DOCUMENT_BONUS: f64 = 0.75;
+const IN_DOCUMENT_COUNT_BONUS: f64 = 0.05;
+const MATCHING_CASE_BONUS: f64 = 0.25;
+const ADJACENT_KEY_COST: f64 = 0.5;
+
+const KEYBOARD_ROWS: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// Information about the text a misspelled word was found in.
+#[derive(Debug, Default)]
+pub struct SuggestionContext {
+    /// Lowercased words of the document and how often they occur
+    document_words: HashMap<String, usize>,
+}
+
+impl SuggestionContext {
+    pub fn new(document: Option<&str>) -> Self {
+        let mut document_words: HashMap<String, usize> = HashMap::new();
+        if let Some(text) = document {
+            for token in text.split(|c: char| !c.is_alphanumeric()) {
+                for split_word in splitter::split(token) {
+                    *document_words
+                        .entry(split_word.word.to_lowercase())
+                        .or_default() += 1;
+                }
+            }
+        }
+        Self { document_words }
+    }
+
+    fn occurrences(&self, word: &str) -> usize {
+        self.document_words
+            .get(&word.to_lowercase())
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// A suggestion offered by one or more dictionaries.
+#[derive(Debug, Clone, PartialEq)]
+struct Candidate {
+    word: String,
+    /// Best position of this word in any dictionary's suggestion list
+    rank: usize,
+    /// Number of dictionaries that suggested this word
+    sources: usize,
+}
+
+/// Merge the suggestion lists of all dictionaries, keeping the order in which
+/// candidates were first seen.
+fn collect_candidates(sources: &[Vec<String>]) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = Vec::new();
+    for source in sources {
+        for (rank, word) in source.iter().enumerate() {
+            match candidates.iter_mut().find(|c| &c.word == word) {
+                Some(existing) => {
+                    existing.rank = existing.rank.min(rank);
+                    existing.sources += 1;
+                }
+                None => candidates.push(Candidate {
+                    word: word.clone(),
+                    rank,
+                    sources: 1,
+                }),
+            }
+        }
+    }
+    candidates
+}
+
+/// Rank suggestions from all dictionaries for `word` and return the best `max_count`.
+/// `is_known_term` reports whether a candidate is a known programming term.
+pub fn rank_suggestions(
+    word: &str,
+    sources: &[Vec<String>],
+    context: &SuggestionContext,
+    is_known_term: impl Fn(&str) -> bool,
+    max_count: usize,
+) -> Vec<String> {
+    let mut scored: Vec<(f64, Candidate)> = collect_candidates(sources)
+        .into_iter()
+        .map(|candidate| {
+            let score = score_candidate(word, &candidate, context, &is_known_term);
+            (score, candidate)
+        })
+        .collect();
+    // Stable sort, so equal scores keep dictionary order
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+        .into_iter()
+        .take(max_count)
+        .map(|(_, candidate)| candidate.word)
+        .collect()
+}
+
+fn score_candidate(
+    word: &str,
+    candidate: &Candidate,
+    context: &SuggestionContext,
+    is_known_term: &impl Fn(&str) -> bool,
+) -> f64 {
+    let mut score = -keyboard_distance(word, &candidate.word);
+    score -= candidate.rank as f64 * DICTIONARY_RANK_WEIGHT;
+    score += (candidate.sources - 1) as f64 * MULTIPLE_SOURCES_BONUS;
+    if is_known_term(&candidate.word) {
+        score += KNOWN_TERM_BONUS;
+    }
+    let occurrences = context.occurrences(&candidate.word);
+    if occurrences > 0 {
+        score += IN_DOCUMENT_BONUS + occurrences.min(10) as f64 * IN_DOCUMENT_COUNT_BONUS;
+    }
+    if WordCase::of(word) == WordCase::of(&candidate.word) {
+        score += MATCHING_CASE_BONUS;
+    }
+    score
+}
+
+/// Position of a key on a QWERTY keyboard as (row, doubled column).
+/// Columns are doubled so the half-key stagger between rows stays an integer.
+fn key_position(c: char) -> Option<(i32, i32)> {
+    KEYBOARD_ROWS.iter().enumerate().find_map(|(row, keys)| {
+        keys.find(c)
+            .map(|col| (row as i32, 2 * col as i32 + row as i32))
+    })
+}
+
+fn are_adjacent_keys(a: char, b: char) -> bool {
+    match (key_position(a), key_position(b)) {
+        (Some((row_a, col_a)), Some((row_b, col_b))) => {
+            let (rows, cols) = ((row_a - row_b).abs(), (col_a - col_b).abs());
+            (rows == 0 && cols == 2) || (rows == 1 && cols == 1)
+        }
+        _ => false,
+    }
+}
+
+/// Case-insensitive Damerau-Levenshtein (optimal string alignment) distance,
+/// where substituting a neighbouring key only costs half an edit.
+fn keyboard_distance(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut rows = vec![vec![0.0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i as f64;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j as f64;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = if a[i - 1] == b[j - 1] {
+                0.0
+            } else if are_adjacent_keys(a[i - 1], b[j - 1]) {
+                ADJACENT_KEY_COST
+            } else {
+                1.0
+            };
+            let mut best = (rows[i - 1][j] + 1.0)
+                .min(rows[i][j - 1] + 1.0)
+                .min(rows[i - 1][j - 1] + substitution);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1.0);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_sources(sources: &[&[&str]]) -> Vec<Vec<String>> {
+        sources
+            .iter()
+            .map(|s| s.iter().map(|w| w.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_collect_candidates_merges_sources() {
+        let sources = to_sources(&[&["apple", "banana"], &["banana", "cherry"]]);
+        let candidates = collect_candidates(&sources);
+        assert_eq!(
+            candidates,
+            vec![
+                Candidate {
+                    word: "apple".to_string(),
+                    rank: 0,
+                    sources: 1
+                },
+                Candidate {
+                    word: "banana".to_string(),
+                    rank: 0,
+                    sources: 2
+                },
+                Candidate {
+                    word: "cherry".to_string(),
+                    rank: 1,
+                    sources: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyboard_distance() {
+        assert_eq!(keyboard_distance("word", "word"), 0.0);
+        assert_eq!(keyboard_distance("Word", "word"), 0.0);
+        // 's' is next to 'a' on the keyboard, 'p' is not
+        assert_eq!(keyboard_distance("cst", "cat"), 0.5);
+        assert_eq!(keyboard_distance("cpt", "cat"), 1.0);
+        // Transposition is a single edit
+        assert_eq!(keyboard_distance("teh", "the"), 1.0);
+        assert_eq!(keyboard_distance("wrld", "world"), 1.0);
+    }
+
+    #[test]
+    fn test_closer_words_rank_first() {
+        let sources = to_sources(&[&["tester", "tests", "test"]]);
+        let context = SuggestionContext::default();
+        let ranked = rank_suggestions("testz", &sources, &context, |_| false, 5);
+        // "tests" is a neighbouring key substitution, the others need a full edit
+        assert_eq!(ranked[0], "tests");
+    }
+
+    #[test]
+    fn test_document_words_rank_first() {
+        let sources = to_sources(&[&["bat", "bet"]]);
+        let context = SuggestionContext::default();
+        let ranked = rank_suggestions("bst", &sources, &context, |_| false, 5);
+        assert_eq!(ranked, vec!["bat", "bet"]);
+
+        let context = SuggestionContext::new(Some("let betAmount = place_bet();"));
+        let ranked = rank_suggestions("bst", &sources, &context, |_| false, 5);
+        assert_eq!(ranked, vec!["bet", "bat"]);
+    }
+
+    #[test]
+    fn test_known_terms_rank_first() {
+        let sources = to_sources(&[&["cont", "const"]]);
+        let context = SuggestionContext::default();
+        let ranked = rank_suggestions("conts", &sources, &context, |w| w == "const", 5);
+        assert_eq!(ranked, vec!["const", "cont"]);
+    }
+
+    #[test]
+    fn test_matching_case_ranks_first() {
+        let sources = to_sources(&[&["WORLD", "World"]]);
+        let context = SuggestionContext::default();
+        let ranked = rank_suggestions("Wrld", &sources, &context, |_| false, 5);
+        assert_eq!(ranked, vec!["World", "WORLD"]);
+    }
+
+    #[test]
+    fn test_max_count() {
+        let sources = to_sources(&[&["apple", "banana", "cherry"], &["date"]]);
+        let context = SuggestionContext::default();
+        let ranked = rank_suggestions("apple", &sources, &context, |_| false, 2);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0], "apple");
+        let ranked = rank_suggestions("apple", &sources, &context, |_| false, 10);
+        assert_eq!(ranked.len(), 4);
+    }
+}
@@ -44,3 +44,156 @@ fn test_custom_words() {
         assert!(!not_expected.contains(&result.word.as_str()));
     }
 }
+
+#[test]
+fn test_preload_dictionaries() {
+    let processor = get_processor(None);
+    let mut progress = vec![];
+    let results = processor.preload_dictionaries(|id, index, total| {
+        progress.push((id.to_string(), index, total));
+    });
+    assert_eq!(progress.len(), results.len());
+    for (index, ((id, seen_index, total), (result_id, _))) in
+        progress.iter().zip(&results).enumerate()
+    {
+        assert_eq!(id, result_id);
+        assert_eq!(*seen_index, index);
+        assert_eq!(*total, results.len());
+    }
+    // Built in, so it loads without network access
+    assert!(results.contains(&("codebook".to_string(), true)));
+    assert!(results.iter().any(|(id, _)| id == "en_us"));
+}
+
+#[test]
+fn test_overrides() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    std::fs::write(
+        root.join("codebook.toml"),
+        r#"
+words = ["nope"]
+
+[[overrides]]
+paths = ["tests/**"]
+words = ["fixtureword"]
+
+[[overrides]]
+languages = ["python"]
+flag_words = ["nope"]
+"#,
+    )
+    .unwrap();
+    let config = Arc::new(codebook_config::CodebookConfig::load(Some(root)).unwrap());
+    let processor = Codebook::new(config).unwrap();
+    let words = |text: &str, language: LanguageType, path: &str| -> Vec<String> {
+        processor
+            .spell_check(
+                text,
+                Some(language),
+                Some(&root.join(path).to_string_lossy()),
+            )
+            .into_iter()
+            .map(|location| location.word)
+            .collect()
+    };
+
+    assert!(words("fixtureword", LanguageType::Text, "tests/data.txt").is_empty());
+    assert_eq!(
+        words("fixtureword", LanguageType::Text, "src/data.txt"),
+        vec!["fixtureword"]
+    );
+    assert_eq!(
+        words("# nope", LanguageType::Python, "src/main.py"),
+        vec!["nope"]
+    );
+    assert!(words("// nope", LanguageType::Rust, "src/main.rs").is_empty());
+    assert!(processor.should_flag_word("nope", Some(&root.join("src/main.py").to_string_lossy())));
+    assert!(!processor.should_flag_word("nope", Some(&root.join("src/main.rs").to_string_lossy())));
+}
+
+#[test]
+fn test_case_sensitive_words() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    std::fs::write(
+        root.join("codebook.toml"),
+        "words = [\"and\"]\ncase_sensitive_words = [\"GitHub\", \"iOS\", \"PostgreSQL\"]\n",
+    )
+    .unwrap();
+    let config = Arc::new(codebook_config::CodebookConfig::load(Some(root)).unwrap());
+    let processor = Codebook::new(config).unwrap();
+    let path = root.join("notes.txt");
+    let path = path.to_str();
+
+    let mut misspelled: Vec<String> = processor
+        .spell_check(
+            "GitHub and Github and iOS and Ios and PostgreSQL",
+            Some(LanguageType::Text),
+            path,
+        )
+        .into_iter()
+        .map(|location| location.word)
+        .collect();
+    misspelled.sort();
+    assert_eq!(misspelled, vec!["Github", "Ios"]);
+    assert_eq!(
+        processor.get_correct_casing("Github", path).as_deref(),
+        Some("GitHub")
+    );
+    assert_eq!(
+        processor.get_suggestions("Ios", None, path),
+        Some(vec!["iOS".to_string()])
+    );
+}
+
+#[test]
+fn test_case_sensitive_words_in_constants() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    std::fs::write(
+        root.join("codebook.toml"),
+        "case_sensitive_words = [\"GitHub\", \"iOS\"]\n",
+    )
+    .unwrap();
+    let config = Arc::new(codebook_config::CodebookConfig::load(Some(root)).unwrap());
+    let processor = Codebook::new(config).unwrap();
+    let path = root.join("main.rs");
+    let path = path.to_str();
+
+    let misspelled: Vec<String> = processor
+        .spell_check(
+            "const GITHUB_TOKEN: &str = \"\";\nconst IOS_VERSION: u32 = 17;\n",
+            Some(LanguageType::Rust),
+            path,
+        )
+        .into_iter()
+        .map(|location| location.word)
+        .collect();
+    assert!(misspelled.is_empty(), "{misspelled:?}");
+    assert_eq!(processor.get_correct_casing("GITHUB", path), None);
+    assert_eq!(processor.get_correct_casing("IOS", path), None);
+}
+
+#[test]
+fn test_explain_word() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    std::fs::write(root.join("codebook.toml"), "flag_words = [\"wrld\"]\n").unwrap();
+    let config = Arc::new(codebook_config::CodebookConfig::load(Some(root)).unwrap());
+    let processor = Codebook::new(config).unwrap();
+    let path = root.join("main.rs");
+
+    let explanation = processor.explain_word(
+        "wrld",
+        "calc_wrld",
+        Some(LanguageType::Rust),
+        None,
+        path.to_str(),
+    );
+    assert_eq!(explanation.parts, vec!["calc", "wrld"]);
+    assert!(explanation.flagged);
+    assert_eq!(explanation.correct_casing, None);
+    // The built-in word list is always available
+    assert!(explanation.dictionaries.contains(&"codebook".to_string()));
+}
@@ -98,3 +98,31 @@ fn test_rust_struct() {
         assert_eq!(result.locations, expect.locations);
     }
 }
+
+#[test]
+fn test_rust_directives() {
+    utils::init_logging();
+    let processor = utils::get_processor();
+    let sample_text = r#"
+        // codebook:words wrld
+        fn calculat_age() {} // codebook:ignore-line
+        // codebook:ignore-next-line
+        let usr_nme = "helo";
+        let usr_agge = "wrld";
+        // codebook:disable
+        let frst = 1;
+        // codebook:enable
+        let secnd = 2;
+    "#;
+    let expected = vec!["agge", "secnd"];
+    let binding = processor
+        .spell_check(sample_text, Some(LanguageType::Rust), None)
+        .to_vec();
+    let mut misspelled = binding
+        .iter()
+        .map(|r| r.word.as_str())
+        .collect::<Vec<&str>>();
+    misspelled.sort();
+    println!("Misspelled words: {misspelled:?}");
+    assert_eq!(misspelled, expected);
+}
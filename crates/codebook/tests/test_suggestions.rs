@@ -10,7 +10,7 @@ pub fn get_processor() -> Codebook {
 #[test]
 fn test_suggestions() {
     let processor = get_processor();
-    let suggestions = processor.get_suggestions("testz");
+    let suggestions = processor.get_suggestions("testz", None, None);
     println!("Suggestion words: {suggestions:?}");
     assert!(!suggestions.unwrap().is_empty());
 }
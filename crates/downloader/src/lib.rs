@@ -1,20 +1,67 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use fs2::FileExt;
 use log::info;
+use reqwest::Proxy;
 use reqwest::blocking::{Client, Response};
 use reqwest::header::{IF_MODIFIED_SINCE, LAST_MODIFIED};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::{BufReader, Read};
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
 use tempfile::NamedTempFile;
 
 const METADATA_FILE: &str = "_metadata.json";
+const LOCK_FILE: &str = "_metadata.lock";
+/// Prefix of the temporary files downloads and metadata are written to
+const TEMP_PREFIX: &str = ".codebook-tmp";
+/// Files younger than this are never garbage collected
+const GC_GRACE_PERIOD: Duration = Duration::from_secs(3600);
+/// How often the last use of a file is recorded, to avoid a metadata write on every use
+const LAST_USED_RESOLUTION: Duration = Duration::from_secs(24 * 3600);
 const TWO_WEEKS: u64 = 14 * 24 * 3600;
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Replace the start of download URLs, e.g. to fetch from an internal mirror.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlRewrite {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloaderOptions {
+    /// The first rewrite matching a URL is applied
+    pub url_rewrites: Vec<UrlRewrite>,
+    /// Proxy for all requests, otherwise the standard proxy environment variables are used
+    pub proxy: Option<String>,
+    pub connect_timeout: Duration,
+    /// Time allowed for a whole response to be read
+    pub read_timeout: Duration,
+    /// How long a downloaded file is used before checking for a newer version
+    pub refresh_interval: Duration,
+    /// Never touch the network, only use files already in the cache
+    pub offline: bool,
+}
+
+impl Default for DownloaderOptions {
+    fn default() -> Self {
+        Self {
+            url_rewrites: Vec::new(),
+            proxy: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            refresh_interval: Duration::from_secs(TWO_WEEKS),
+            offline: false,
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Metadata {
@@ -27,101 +74,282 @@ struct FileEntry {
     last_checked: DateTime<Utc>,
     last_modified: Option<DateTime<Utc>>,
     content_hash: String,
+    /// Missing for files downloaded by older versions
+    #[serde(default)]
+    last_used: Option<DateTime<Utc>>,
+}
+
+impl FileEntry {
+    fn last_used(&self) -> DateTime<Utc> {
+        self.last_used.unwrap_or(self.last_checked)
+    }
+}
+
+/// A downloaded file, as listed by `Downloader::entries`.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub url: String,
+    pub path: PathBuf,
+    /// None if the file is gone
+    pub size: Option<u64>,
+    pub last_checked: DateTime<Utc>,
+    pub last_used: DateTime<Utc>,
+    pub content_hash: String,
+}
+
+/// What a file looked like when its hash was last checked
+#[derive(Debug, PartialEq)]
+struct FileStamp {
+    content_hash: String,
+    size: u64,
+    modified: Option<SystemTime>,
 }
 
+impl FileStamp {
+    fn read(path: &Path, content_hash: &str) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            content_hash: content_hash.to_string(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+#[derive(Debug)]
 pub struct Downloader {
     cache_dir: PathBuf,
     metadata: RwLock<Metadata>,
     client: Client,
+    options: DownloaderOptions,
+    /// Files whose hash was checked, so they aren't hashed on every use
+    verified: RwLock<HashMap<PathBuf, FileStamp>>,
 }
 
 impl Downloader {
     pub fn new(cache_dir: impl AsRef<Path>) -> Result<Self> {
+        Self::with_options(cache_dir, DownloaderOptions::default())
+    }
+
+    pub fn with_options(cache_dir: impl AsRef<Path>, options: DownloaderOptions) -> Result<Self> {
         let cache_dir = cache_dir.as_ref().to_path_buf();
         fs::create_dir_all(&cache_dir)?;
         info!("Cache folder at: {:?}", cache_dir);
 
-        let metadata_path = cache_dir.join(METADATA_FILE);
-        let metadata = if metadata_path.exists() {
-            // Try to parse the existing metadata file
-            match File::open(&metadata_path).and_then(|file| Ok(serde_json::from_reader(file)?)) {
-                Ok(metadata) => metadata,
-                Err(e) => {
-                    // Log the error but continue with a fresh metadata file
-                    log::warn!("Failed to load metadata file: {}, creating a new one", e);
-                    Metadata {
-                        files: HashMap::new(),
-                    }
-                }
-            }
-        } else {
-            Metadata {
-                files: HashMap::new(),
-            }
-        };
+        let metadata = read_metadata(&cache_dir.join(METADATA_FILE)).unwrap_or_else(|| Metadata {
+            files: HashMap::new(),
+        });
+
+        let mut client = Client::builder()
+            .connect_timeout(options.connect_timeout)
+            .timeout(options.read_timeout);
+        if let Some(proxy) = &options.proxy {
+            client = client.proxy(Proxy::all(proxy)?);
+        }
 
         Ok(Self {
             cache_dir,
             metadata: RwLock::new(metadata),
-            client: Client::new(),
+            client: client.build()?,
+            options,
+            verified: RwLock::new(HashMap::new()),
         })
     }
 
     pub fn get(&self, url: &str) -> Result<PathBuf> {
-        // First check with read lock
-        let (needs_update, file_exists) = {
-            let metadata = self.metadata.read().unwrap();
-            let entry = metadata.files.get(url);
-
-            match entry {
-                Some(e) => {
-                    let needs_update =
-                        e.last_checked.timestamp() + TWO_WEEKS as i64 <= Utc::now().timestamp();
-                    let file_exists = e.path.exists();
-                    (Some(needs_update), file_exists)
+        let path = self.fetch(url, None)?;
+        self.mark_used(url);
+        Ok(path)
+    }
+
+    /// Like `get`, but the file must have the given SHA-256 hash, either as bare
+    /// hex or prefixed with `sha256:`. Cached files that don't match are fetched
+    /// again and downloads that don't match are rejected.
+    pub fn get_verified(&self, url: &str, expected_hash: &str) -> Result<PathBuf> {
+        let expected = expected_hash
+            .strip_prefix("sha256:")
+            .unwrap_or(expected_hash)
+            .to_lowercase();
+        let path = self.fetch(url, Some(&expected))?;
+        self.mark_used(url);
+        Ok(path)
+    }
+
+    /// All downloaded files, sorted by URL.
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        let metadata = self.metadata.read().unwrap();
+        let mut entries: Vec<CacheEntry> = metadata
+            .files
+            .iter()
+            .map(|(url, entry)| CacheEntry {
+                url: url.clone(),
+                path: entry.path.clone(),
+                size: fs::metadata(&entry.path).ok().map(|m| m.len()),
+                last_checked: entry.last_checked,
+                last_used: entry.last_used(),
+                content_hash: entry.content_hash.clone(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+        entries
+    }
+
+    /// Remove files that haven't been used for `max_age`. Returns their URLs.
+    pub fn prune(&self, max_age: Duration) -> Result<Vec<String>> {
+        let cutoff = Utc::now() - chrono::Duration::from_std(max_age)?;
+        self.update_metadata(|metadata| {
+            let mut removed: Vec<String> = metadata
+                .files
+                .iter()
+                .filter(|(_, entry)| entry.last_used() < cutoff)
+                .map(|(url, _)| url.clone())
+                .collect();
+            removed.sort();
+            for url in &removed {
+                let entry = metadata.files.remove(url).unwrap();
+                if entry.path.exists() {
+                    fs::remove_file(entry.path)?;
                 }
-                None => (None, false),
             }
-        };
+            Ok(removed)
+        })
+    }
 
-        // If the file doesn't exist but we have metadata, treat it as a new download
-        if !file_exists && needs_update.is_some() {
-            return self.download_new(url);
+    /// Forget a cached file, e.g. because it turned out to be unusable, so the
+    /// next `get` downloads it again.
+    pub fn invalidate(&self, url: &str) -> Result<()> {
+        self.update_metadata(|metadata| {
+            if let Some(entry) = metadata.files.remove(url)
+                && entry.path.exists()
+            {
+                fs::remove_file(entry.path)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Remove files in the cache dir that no metadata entry refers to, such as
+    /// leftovers of interrupted downloads, and entries whose file is gone.
+    /// Only files named like the ones the downloader creates are removed, other
+    /// files and sub-directories are left alone. Returns the number of files
+    /// removed.
+    pub fn collect_garbage(&self) -> Result<usize> {
+        self.update_metadata(|metadata| {
+            metadata.files.retain(|_, entry| entry.path.exists());
+            let referenced: HashSet<_> = metadata
+                .files
+                .values()
+                .filter_map(|entry| entry.path.file_name().map(|n| n.to_os_string()))
+                .collect();
+            let mut removed = 0;
+            for entry in fs::read_dir(&self.cache_dir)? {
+                let entry = entry?;
+                let name = entry.file_name();
+                if !is_download_name(&name) || referenced.contains(&name) {
+                    continue;
+                }
+                let file_meta = entry.metadata()?;
+                // Another process might still be downloading into a fresh temp file
+                let recent = file_meta
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.elapsed().ok())
+                    .is_none_or(|age| age < GC_GRACE_PERIOD);
+                if file_meta.is_dir() || recent {
+                    continue;
+                }
+                info!("Removing orphaned cache file: {:?}", entry.path());
+                fs::remove_file(entry.path())?;
+                removed += 1;
+            }
+            Ok(removed)
+        })
+    }
+
+    fn fetch(&self, url: &str, expected: Option<&str>) -> Result<PathBuf> {
+        if self.options.offline {
+            return self
+                .get_cached(url, expected)
+                .ok_or_else(|| anyhow::anyhow!("Offline and not cached: {}", url));
         }
+        // (needs_update, usable)
+        let state = {
+            let metadata = self.metadata.read().unwrap();
+            metadata.files.get(url).map(|e| {
+                let needs_update = e.last_checked.timestamp()
+                    + self.options.refresh_interval.as_secs() as i64
+                    <= Utc::now().timestamp();
+                (needs_update, self.is_usable(e, expected))
+            })
+        };
 
-        match needs_update {
-            Some(true) => self.try_update(url),
-            Some(false) => Ok(self.metadata.read().unwrap().files[url].path.clone()),
-            None => self.download_new(url),
+        match state {
+            None => self.download_new(url, expected),
+            Some((_, false)) => {
+                log::warn!(
+                    "Cached file is missing or corrupted, downloading again: {}",
+                    url
+                );
+                self.download_new(url, expected)
+            }
+            Some((true, true)) => self.try_update(url, expected),
+            Some((false, true)) => Ok(self.metadata.read().unwrap().files[url].path.clone()),
         }
         .or_else(|e| {
             log::error!("Failed to update, using cached version: {}", e);
-            let entry = {
-                let metadata = self.metadata.read().unwrap();
-                metadata
-                    .files
-                    .get(url)
-                    .map(|file_info| file_info.path.clone())
-            };
-            match entry {
-                Some(path) => {
-                    if path.exists() {
-                        Ok(path)
-                    } else {
-                        // If fallback path doesn't exist, try to download anyway
-                        self.download_new(url)
-                    }
-                }
-                None => {
-                    // URL not found in the files hashmap, try to download it
-                    log::warn!("URL not found in cache, attempting fresh download: {}", url);
-                    self.download_new(url)
-                }
-            }
+            self.get_cached(url, expected).ok_or(e)
         })
     }
 
-    fn try_update(&self, url: &str) -> Result<PathBuf> {
+    /// Whether a cache entry's file still exists and has the content it was
+    /// downloaded with (and the expected one, if given). Files are only hashed
+    /// again when their size or modification time changed.
+    fn is_usable(&self, entry: &FileEntry, expected: Option<&str>) -> bool {
+        if expected.is_some_and(|hash| hash != entry.content_hash) {
+            return false;
+        }
+        let Some(stamp) = FileStamp::read(&entry.path, &entry.content_hash) else {
+            return false;
+        };
+        if self.verified.read().unwrap().get(&entry.path) == Some(&stamp) {
+            return true;
+        }
+        match compute_file_hash(&entry.path) {
+            Ok(hash) if hash == entry.content_hash => {
+                self.verified
+                    .write()
+                    .unwrap()
+                    .insert(entry.path.clone(), stamp);
+                true
+            }
+            Ok(_) => {
+                log::warn!("Cached file is corrupted: {:?}", entry.path);
+                false
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Path of a previously downloaded file, if it is still in the cache and intact.
+    fn get_cached(&self, url: &str, expected: Option<&str>) -> Option<PathBuf> {
+        let metadata = self.metadata.read().unwrap();
+        let entry = metadata.files.get(url)?;
+        self.is_usable(entry, expected).then(|| entry.path.clone())
+    }
+
+    /// The URL to download from, files are still cached under their original URL.
+    fn resolve_url(&self, url: &str) -> String {
+        self.options
+            .url_rewrites
+            .iter()
+            .find_map(|rewrite| {
+                url.strip_prefix(rewrite.from.as_str())
+                    .map(|rest| format!("{}{}", rewrite.to, rest))
+            })
+            .unwrap_or_else(|| url.to_string())
+    }
+
+    fn try_update(&self, url: &str, expected: Option<&str>) -> Result<PathBuf> {
         // Get last modified time with read lock
         let last_modified = {
             self.metadata
@@ -131,21 +359,17 @@ impl Downloader {
                 .get(url)
                 .and_then(|e| e.last_modified)
         };
-        // log::info!("{:?}", last_modified);
-        // log::info!("URL {:?}", url);
 
-        let mut request = self.client.get(url);
+        let mut request = self.client.get(self.resolve_url(url));
         if let Some(lm) = last_modified {
             request = request.header(IF_MODIFIED_SINCE, lm.with_timezone(&Utc).to_rfc2822());
         }
-        // log::info!("{:?}", request);
 
         let response = request.send()?;
-        // log::info!("RESPONSE {:?}", response);
 
         match response.status().as_u16() {
             304 => self.update_check_time(url),
-            200 => self.handle_updated_response(url, response),
+            200 => self.handle_updated_response(url, response, expected),
             status => {
                 let _ = self.update_check_time(url);
                 Err(anyhow::anyhow!("Unexpected status code: {}", status))
@@ -153,115 +377,199 @@ impl Downloader {
         }
     }
 
-    fn handle_updated_response(&self, url: &str, response: Response) -> Result<PathBuf> {
+    fn handle_updated_response(
+        &self,
+        url: &str,
+        response: Response,
+        expected: Option<&str>,
+    ) -> Result<PathBuf> {
         let last_modified = parse_last_modified(&response);
         let temp_file = self.download_to_temp(response)?;
         let new_hash = compute_file_hash(temp_file.path())?;
+        verify_hash(url, &new_hash, expected)?;
         let old_hash = {
             let metadata = self
                 .metadata
                 .read()
                 .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
-            metadata.files.get(url).unwrap().content_hash.clone()
+            metadata.files.get(url).map(|e| e.content_hash.clone())
         };
-        if new_hash == old_hash {
+        if old_hash.as_ref() == Some(&new_hash) {
             self.update_check_time(url)
         } else {
-            self.replace_file(url, temp_file, last_modified, new_hash)
+            self.store_file(url, temp_file, last_modified, new_hash)
         }
     }
 
-    fn download_new(&self, url: &str) -> Result<PathBuf> {
-        let response = self.client.get(url).send()?;
+    fn download_new(&self, url: &str, expected: Option<&str>) -> Result<PathBuf> {
+        let response = self
+            .client
+            .get(self.resolve_url(url))
+            .send()?
+            .error_for_status()?;
         let last_modified = parse_last_modified(&response);
         let temp_file = self.download_to_temp(response)?;
         let new_hash = compute_file_hash(temp_file.path())?;
-        self.store_new_file(url, temp_file, last_modified, new_hash)
+        verify_hash(url, &new_hash, expected)?;
+        self.store_file(url, temp_file, last_modified, new_hash)
     }
 
     fn download_to_temp(&self, mut response: Response) -> Result<NamedTempFile> {
-        let mut temp_file = NamedTempFile::new_in(&self.cache_dir)?;
-        std::io::copy(&mut response, &mut temp_file)?;
-        Ok(temp_file)
-    }
-
-    fn store_new_file(
-        &self,
-        url: &str,
-        temp_file: NamedTempFile,
-        last_modified: Option<DateTime<Utc>>,
-        content_hash: String,
-    ) -> Result<PathBuf> {
-        let filename = hash_url(url);
-        let path = self.cache_dir.join(filename);
-        temp_file.persist(&path)?;
-
-        let entry = FileEntry {
-            path: path.clone(),
-            last_checked: Utc::now(),
-            last_modified,
-            content_hash,
-        };
+        let expected_len = response.content_length();
+        let mut temp_file = temp_file_in(&self.cache_dir)?;
+        let len = std::io::copy(&mut response, &mut temp_file)?;
+        if let Some(expected_len) = expected_len
+            && len != expected_len
         {
-            let mut metadata = self.metadata.write().unwrap();
-            metadata.files.insert(url.to_string(), entry);
+            anyhow::bail!(
+                "Truncated download: got {} of {} bytes from {}",
+                len,
+                expected_len,
+                response.url()
+            );
         }
-        self.save_metadata()?;
-        Ok(path)
+        Ok(temp_file)
     }
 
-    fn replace_file(
+    fn store_file(
         &self,
         url: &str,
         temp_file: NamedTempFile,
         last_modified: Option<DateTime<Utc>>,
         content_hash: String,
     ) -> Result<PathBuf> {
-        let new_path: PathBuf;
-        {
-            let mut metadata = self.metadata.write().unwrap();
-            let entry = metadata.files.get_mut(url).unwrap();
-            let old_path = entry.path.clone();
-
-            new_path = self.cache_dir.join(hash_url(url));
-            temp_file.persist(&new_path)?;
-
-            // Remove old file if it's different
-            if old_path != new_path && old_path.exists() {
-                fs::remove_file(old_path)?;
+        let path = self.cache_dir.join(hash_url(url));
+        self.update_metadata(|metadata| {
+            temp_file.persist(&path)?;
+            let entry = FileEntry {
+                path: path.clone(),
+                last_checked: Utc::now(),
+                last_modified,
+                content_hash,
+                last_used: Some(Utc::now()),
+            };
+            // Remove the old file if it was stored somewhere else
+            if let Some(old) = metadata.files.insert(url.to_string(), entry)
+                && old.path != path
+                && old.path.exists()
+            {
+                fs::remove_file(old.path)?;
             }
+            Ok(path.clone())
+        })
+    }
 
-            entry.path = new_path.clone();
-            entry.last_checked = Utc::now();
-            entry.last_modified = last_modified;
-            entry.content_hash = content_hash;
+    /// Record that a file was used, at most once per `LAST_USED_RESOLUTION`.
+    fn mark_used(&self, url: &str) {
+        let recent = Utc::now() - chrono::Duration::from_std(LAST_USED_RESOLUTION).unwrap();
+        let needs_update = {
+            let metadata = self.metadata.read().unwrap();
+            metadata
+                .files
+                .get(url)
+                .is_some_and(|e| e.last_used.is_none_or(|used| used < recent))
+        };
+        if !needs_update {
+            return;
+        }
+        let result = self.update_metadata(|metadata| {
+            if let Some(entry) = metadata.files.get_mut(url) {
+                entry.last_used = Some(Utc::now());
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::warn!("Failed to record use of {}: {}", url, e);
         }
-
-        self.save_metadata()?;
-        Ok(new_path)
     }
 
     fn update_check_time(&self, url: &str) -> Result<PathBuf> {
-        let path: PathBuf;
-        {
-            let mut metadata = self.metadata.write().unwrap();
-            let entry = metadata.files.get_mut(url).unwrap();
+        let known = self.metadata.read().unwrap().files.get(url).cloned();
+        self.update_metadata(|metadata| {
+            // Another process may have dropped the entry in the meantime
+            let entry = match metadata.files.get_mut(url) {
+                Some(entry) => entry,
+                None => metadata
+                    .files
+                    .entry(url.to_string())
+                    .or_insert(known.ok_or_else(|| anyhow::anyhow!("Not in the cache: {}", url))?),
+            };
             entry.last_checked = Utc::now();
-            path = entry.path.clone();
+            Ok(entry.path.clone())
+        })
+    }
+
+    /// Change the metadata while holding a lock on the cache, so processes sharing
+    /// it don't lose each other's updates. The metadata is re-read from disk first
+    /// and written back atomically.
+    fn update_metadata<T>(&self, f: impl FnOnce(&mut Metadata) -> Result<T>) -> Result<T> {
+        let lock_file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.cache_dir.join(LOCK_FILE))?;
+        lock_file.lock_exclusive()?;
+
+        let result = (|| {
+            let mut metadata = self.metadata.write().unwrap();
+            if let Some(on_disk) = read_metadata(&self.cache_dir.join(METADATA_FILE)) {
+                *metadata = on_disk;
+            }
+            let result = f(&mut metadata);
+            // Also save the changes made before a failure
+            let mut temp_file = temp_file_in(&self.cache_dir)?;
+            serde_json::to_writer_pretty(&mut temp_file, metadata.deref())?;
+            temp_file.persist(self.cache_dir.join(METADATA_FILE))?;
+            result
+        })();
+
+        FileExt::unlock(&lock_file)?;
+        result
+    }
+}
+
+fn read_metadata(path: &Path) -> Option<Metadata> {
+    if !path.exists() {
+        return None;
+    }
+    match File::open(path).and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?)) {
+        Ok(metadata) => Some(metadata),
+        Err(e) => {
+            // Log the error but continue with a fresh metadata file
+            log::warn!("Failed to load metadata file: {}, creating a new one", e);
+            None
         }
-        self.save_metadata()?;
-        Ok(path)
     }
+}
 
-    fn save_metadata(&self) -> Result<()> {
-        let metadata_path = self.cache_dir.join(METADATA_FILE);
-        let file = File::create(metadata_path)?;
-        let metadata = self.metadata.read().unwrap();
-        serde_json::to_writer_pretty(file, &metadata.deref())?;
-        Ok(())
+fn verify_hash(url: &str, hash: &str, expected: Option<&str>) -> Result<()> {
+    match expected {
+        Some(expected) if expected != hash => Err(anyhow::anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            url,
+            expected,
+            hash
+        )),
+        _ => Ok(()),
     }
 }
 
+fn temp_file_in(dir: &Path) -> std::io::Result<NamedTempFile> {
+    tempfile::Builder::new()
+        .prefix(TEMP_PREFIX)
+        .tempfile_in(dir)
+}
+
+/// Whether a file in the cache dir was created by the downloader: a download
+/// named after the hash of its URL or a temporary file
+fn is_download_name(name: &std::ffi::OsStr) -> bool {
+    let Some(name) = name.to_str() else {
+        return false;
+    };
+    name.starts_with(TEMP_PREFIX)
+        || (name.len() == 64 && name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')))
+}
+
 fn hash_url(url: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(url.as_bytes());
@@ -323,6 +631,248 @@ mod tests {
         assert_eq!(entry.content_hash, compute_file_hash(&entry.path).unwrap());
     }
 
+    #[test]
+    fn test_url_rewrite() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET").path("/mirror/test.txt");
+            then.status(200).body("mirrored content");
+        });
+
+        let temp_dir = tempdir().unwrap();
+        let options = DownloaderOptions {
+            url_rewrites: vec![UrlRewrite {
+                from: "https://example.com/".to_string(),
+                to: server.url("/mirror/"),
+            }],
+            ..Default::default()
+        };
+        let downloader = Downloader::with_options(temp_dir.path(), options).unwrap();
+        let path = downloader.get("https://example.com/test.txt").unwrap();
+
+        mock.assert();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "mirrored content");
+        // Cached under the original URL
+        let metadata = downloader.metadata.read().unwrap();
+        assert!(metadata.files.contains_key("https://example.com/test.txt"));
+    }
+
+    #[test]
+    fn test_offline_mode() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET").path("/test.txt");
+            then.status(200).body("cached content");
+        });
+        let temp_dir = tempdir().unwrap();
+        let url = server.url("/test.txt");
+
+        let offline = DownloaderOptions {
+            offline: true,
+            ..Default::default()
+        };
+        let downloader = Downloader::with_options(temp_dir.path(), offline.clone()).unwrap();
+        assert!(downloader.get(&url).is_err());
+        mock.assert_hits(0);
+
+        let path = Downloader::new(temp_dir.path()).unwrap().get(&url).unwrap();
+        mock.assert_hits(1);
+
+        // Even an outdated file is used without checking for updates
+        let downloader = Downloader::with_options(temp_dir.path(), offline).unwrap();
+        {
+            let mut metadata = downloader.metadata.write().unwrap();
+            let entry = metadata.files.get_mut(&url).unwrap();
+            entry.last_checked = Utc::now() - Duration::weeks(3);
+        }
+        assert_eq!(downloader.get(&url).unwrap(), path);
+        mock.assert_hits(1);
+    }
+
+    #[test]
+    fn test_does_not_cache_error_responses() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/missing.txt");
+            then.status(404).body("Not Found");
+        });
+
+        let temp_dir = tempdir().unwrap();
+        let downloader = Downloader::new(temp_dir.path()).unwrap();
+        assert!(downloader.get(&server.url("/missing.txt")).is_err());
+        assert!(
+            downloader
+                .get_cached(&server.url("/missing.txt"), None)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_verifies_expected_checksum() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET").path("/test.txt");
+            then.status(200).body("test content");
+        });
+        let temp_dir = tempdir().unwrap();
+        let url = server.url("/test.txt");
+        let hash = format!("{:x}", Sha256::digest(b"test content"));
+
+        let downloader = Downloader::new(temp_dir.path()).unwrap();
+        assert!(downloader.get_verified(&url, &"0".repeat(64)).is_err());
+        assert!(downloader.get_cached(&url, None).is_none());
+
+        let path = downloader
+            .get_verified(&url, &format!("sha256:{}", hash))
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "test content");
+        mock.assert_hits(2);
+
+        // A cached file with another hash is fetched again
+        assert!(downloader.get_verified(&url, &"0".repeat(64)).is_err());
+        mock.assert_hits(3);
+    }
+
+    #[test]
+    fn test_redownloads_corrupted_file() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET").path("/test.txt");
+            then.status(200).body("test content");
+        });
+        let temp_dir = tempdir().unwrap();
+        let url = server.url("/test.txt");
+
+        let downloader = Downloader::new(temp_dir.path()).unwrap();
+        let path = downloader.get(&url).unwrap();
+        std::fs::write(&path, "test cont").unwrap();
+
+        let path = downloader.get(&url).unwrap();
+        mock.assert_hits(2);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "test content");
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method("GET").path("/test.txt");
+            then.status(200).body("test content");
+        });
+        let temp_dir = tempdir().unwrap();
+        let url = server.url("/test.txt");
+
+        let downloader = Downloader::new(temp_dir.path()).unwrap();
+        let path = downloader.get(&url).unwrap();
+        downloader.invalidate(&url).unwrap();
+        assert!(!path.exists());
+        assert!(downloader.get_cached(&url, None).is_none());
+
+        downloader.get(&url).unwrap();
+        mock.assert_hits(2);
+    }
+
+    #[test]
+    fn test_shared_cache_keeps_all_entries() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/a.txt");
+            then.status(200).body("a");
+        });
+        server.mock(|when, then| {
+            when.method("GET").path("/b.txt");
+            then.status(200).body("b");
+        });
+        let temp_dir = tempdir().unwrap();
+
+        // Both started before either wrote anything
+        let first = Downloader::new(temp_dir.path()).unwrap();
+        let second = Downloader::new(temp_dir.path()).unwrap();
+        first.get(&server.url("/a.txt")).unwrap();
+        second.get(&server.url("/b.txt")).unwrap();
+
+        let metadata = read_metadata(&temp_dir.path().join(METADATA_FILE)).unwrap();
+        assert!(metadata.files.contains_key(&server.url("/a.txt")));
+        assert!(metadata.files.contains_key(&server.url("/b.txt")));
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/test.txt");
+            then.status(200).body("test content");
+        });
+        let temp_dir = tempdir().unwrap();
+        let downloader = Downloader::new(temp_dir.path()).unwrap();
+        let path = downloader.get(&server.url("/test.txt")).unwrap();
+
+        let old = std::time::SystemTime::now() - GC_GRACE_PERIOD * 2;
+        let orphan = temp_dir.path().join(format!("{}XYZ", TEMP_PREFIX));
+        File::create(&orphan).unwrap().set_modified(old).unwrap();
+        let forgotten = temp_dir.path().join(hash_url("https://example.com/gone"));
+        File::create(&forgotten).unwrap().set_modified(old).unwrap();
+        let fresh = temp_dir.path().join(format!("{}ABC", TEMP_PREFIX));
+        File::create(&fresh).unwrap();
+        // Files the downloader didn't create are never touched
+        let user_file = temp_dir.path().join("thesis.docx");
+        File::create(&user_file).unwrap().set_modified(old).unwrap();
+        let sub_dir = temp_dir.path().join("compiled");
+        std::fs::create_dir(&sub_dir).unwrap();
+        File::open(&path).unwrap().set_modified(old).unwrap();
+
+        // Nothing is collected when a downloader is opened
+        Downloader::new(temp_dir.path()).unwrap();
+        assert!(orphan.exists());
+
+        assert_eq!(downloader.collect_garbage().unwrap(), 2);
+        assert!(!orphan.exists());
+        assert!(!forgotten.exists());
+        assert!(user_file.exists());
+        assert!(fresh.exists());
+        assert!(sub_dir.exists());
+        assert!(path.exists());
+        assert!(temp_dir.path().join(METADATA_FILE).exists());
+    }
+
+    #[test]
+    fn test_list_and_prune() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method("GET").path("/old.txt");
+            then.status(200).body("old");
+        });
+        server.mock(|when, then| {
+            when.method("GET").path("/new.txt");
+            then.status(200).body("new!");
+        });
+        let temp_dir = tempdir().unwrap();
+        let downloader = Downloader::new(temp_dir.path()).unwrap();
+        let old_path = downloader.get(&server.url("/old.txt")).unwrap();
+        downloader.get(&server.url("/new.txt")).unwrap();
+
+        let entries = downloader.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].url, server.url("/new.txt"));
+        assert_eq!(entries[0].size, Some(4));
+        assert_eq!(entries[1].size, Some(3));
+
+        // Write the old usage time to disk, where pruning reads it from
+        downloader
+            .update_metadata(|metadata| {
+                let entry = metadata.files.get_mut(&server.url("/old.txt")).unwrap();
+                entry.last_used = Some(Utc::now() - Duration::days(40));
+                Ok(())
+            })
+            .unwrap();
+        let removed = downloader
+            .prune(std::time::Duration::from_secs(30 * 24 * 3600))
+            .unwrap();
+        assert_eq!(removed, vec![server.url("/old.txt")]);
+        assert!(!old_path.exists());
+        assert_eq!(downloader.entries().len(), 1);
+    }
+
     #[test]
     fn test_returns_cached_file_when_offline() {
         let server = MockServer::start();